@@ -0,0 +1,457 @@
+use wasm_bindgen::prelude::*;
+
+use crate::graph::SceneGraph;
+use crate::transforms::Quat;
+use crate::vec3D::Vec3D;
+
+// longest clock step played at once, avoids jumps after the tab was in background
+const MAX_STEP: f64 = 0.25;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Translation = 0,
+    // euler angles in radians, like Transform::rotation, interpolated as quaternions
+    // along the shortest arc: keys must be less than half a turn apart
+    Rotation = 1,
+    Scale = 2,
+}
+
+// How a keyframe value changes until the next keyframe
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    // holds the value, then jumps
+    Step = 0,
+    // straight line
+    Linear = 1,
+    // smooth curve through the neighbouring keyframes (Catmull-Rom)
+    Cubic = 2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    // seconds from the start of the timeline
+    pub time: f64,
+    pub value: Vec3D,
+    pub interpolation: Interpolation,
+}
+
+impl Keyframe {
+    pub fn new(time: f64, value: Vec3D, interpolation: Interpolation) -> Keyframe {
+        Keyframe {
+            time,
+            value,
+            interpolation,
+        }
+    }
+}
+
+// Keyframes of one channel of one node, sorted by time
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub node: usize,
+    pub channel: Channel,
+    keys: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new(node: usize, channel: Channel) -> Track {
+        Track {
+            node,
+            channel,
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    // keeps the keys sorted, replaces a key at the same time
+    // keys at a non finite time are ignored
+    pub fn insert(&mut self, key: Keyframe) {
+        if !key.time.is_finite() {
+            return;
+        }
+        match self.keys.binary_search_by(|k| k.time.total_cmp(&key.time)) {
+            Ok(i) => self.keys[i] = key,
+            Err(i) => self.keys.insert(i, key),
+        }
+    }
+
+    // time of the last key
+    pub fn end(&self) -> f64 {
+        self.keys.last().map_or(0.0, |key| key.time)
+    }
+
+    // slope of the curve at key i, from its neighbours
+    fn tangent(&self, i: usize) -> Vec3D {
+        let prev = &self.keys[i.saturating_sub(1)];
+        let next = &self.keys[(i + 1).min(self.keys.len() - 1)];
+        let dt = next.time - prev.time;
+        if dt <= 0.0 {
+            return Vec3D::new(0.0, 0.0, 0.0);
+        }
+        (next.value - prev.value).scale(1.0 / dt)
+    }
+
+    // value at `time`, the first and last keys hold before and after the track
+    pub fn sample(&self, time: f64) -> Option<Vec3D> {
+        let first = self.keys.first()?;
+        if time <= first.time || time.is_nan() {
+            return Some(first.value);
+        }
+        // last key at or before time
+        let i = self
+            .keys
+            .partition_point(|key| key.time <= time)
+            .saturating_sub(1);
+        if i == self.keys.len() - 1 {
+            return Some(self.keys[i].value);
+        }
+        let (k0, k1) = (&self.keys[i], &self.keys[i + 1]);
+        let h = k1.time - k0.time;
+        let s = (time - k0.time) / h;
+        if self.channel == Channel::Rotation {
+            return Some(self.sample_rotation(i, s));
+        }
+        let value = match k0.interpolation {
+            Interpolation::Step => k0.value,
+            Interpolation::Linear => k0.value + (k1.value - k0.value).scale(s),
+            Interpolation::Cubic => {
+                // cubic Hermite basis
+                let (s2, s3) = (s * s, s * s * s);
+                k0.value.scale(2.0 * s3 - 3.0 * s2 + 1.0)
+                    + self.tangent(i).scale(h * (s3 - 2.0 * s2 + s))
+                    + k1.value.scale(-2.0 * s3 + 3.0 * s2)
+                    + self.tangent(i + 1).scale(h * (s3 - s2))
+            }
+        };
+        Some(value)
+    }
+
+    // rotation between key i and i + 1, no gimbal lock and constant speed
+    fn sample_rotation(&self, i: usize, s: f64) -> Vec3D {
+        let quat = |i: usize| Quat::from_euler(&self.keys[i].value);
+        let (q0, q1) = (quat(i), quat(i + 1));
+        let q = match self.keys[i].interpolation {
+            Interpolation::Step => q0,
+            Interpolation::Linear => q0.slerp(&q1, s),
+            Interpolation::Cubic => {
+                // squad, the rotation counterpart of the Catmull-Rom curve
+                let prev = quat(i.saturating_sub(1));
+                let next = quat((i + 2).min(self.keys.len() - 1));
+                let q1 = q0.closest(&q1);
+                q0.squad(
+                    &q1,
+                    &q0.squad_control(&prev, &q1),
+                    &q1.squad_control(&q0, &next),
+                    s,
+                )
+            }
+        };
+        q.to_euler()
+    }
+}
+
+// Every track of a scene and the playback state. Node transforms only depend on the
+// timeline time, so a paused timeline can be seeked to render any frame again.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    tracks: Vec<Track>,
+    pub looping: bool,
+    pub playing: bool,
+    // playback rate, 1 is real time
+    pub speed: f64,
+    time: f64,
+    // clock of the previous advance
+    last_clock: Option<f64>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Timeline {
+        Timeline {
+            tracks: Vec::new(),
+            looping: true,
+            playing: true,
+            speed: 1.0,
+            time: 0.0,
+            last_clock: None,
+        }
+    }
+
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn add_keyframe(&mut self, node: usize, channel: Channel, key: Keyframe) {
+        let i = match self
+            .tracks
+            .iter()
+            .position(|t| t.node == node && t.channel == channel)
+        {
+            Some(i) => i,
+            None => {
+                self.tracks.push(Track::new(node, channel));
+                self.tracks.len() - 1
+            }
+        };
+        self.tracks[i].insert(key);
+    }
+
+    pub fn clear(&mut self) {
+        self.tracks.clear();
+        self.time = 0.0;
+    }
+
+    // time of the last key of all tracks
+    pub fn duration(&self) -> f64 {
+        self.tracks.iter().map(Track::end).fold(0.0, f64::max)
+    }
+
+    // current time, always within [0, duration]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    // jumps to `time`, wrapped or clamped to the timeline. NaN and infinities are
+    // ignored, they would stick to the time for good
+    pub fn seek(&mut self, time: f64) {
+        if !time.is_finite() {
+            return;
+        }
+        let duration = self.duration();
+        self.time = if duration <= 0.0 {
+            0.0
+        } else if self.looping {
+            time.rem_euclid(duration)
+        } else {
+            time.clamp(0.0, duration)
+        };
+    }
+
+    // moves the time forward by the clock change since the last call (seconds).
+    // A non finite clock is skipped, the next valid one picks up from the last
+    pub fn advance(&mut self, clock: f64) {
+        if !clock.is_finite() {
+            return;
+        }
+        let elapsed = self
+            .last_clock
+            .map_or(0.0, |last| (clock - last).clamp(0.0, MAX_STEP));
+        self.last_clock = Some(clock);
+        if self.playing {
+            self.seek(self.time + elapsed * self.speed);
+        }
+    }
+
+    // sets the animated transforms of the graph nodes for the current time
+    pub fn apply(&self, graph: &mut SceneGraph) {
+        for track in &self.tracks {
+            let (node, value) = match (graph.node_mut(track.node), track.sample(self.time)) {
+                (Some(node), Some(value)) => (node, value),
+                _ => continue,
+            };
+            match track.channel {
+                Channel::Translation => node.transform.translation = value,
+                Channel::Rotation => node.transform.rotation = value,
+                Channel::Scale => node.transform.scale = value,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f64) -> Vec3D {
+        Vec3D::new(x, 2.0 * x, -x)
+    }
+
+    fn make_track(channel: Channel, interpolation: Interpolation, keys: &[(f64, f64)]) -> Track {
+        let mut track = Track::new(0, channel);
+        for &(time, x) in keys {
+            track.insert(Keyframe::new(time, v(x), interpolation));
+        }
+        track
+    }
+
+    fn assert_sample(track: &Track, time: f64, x: f64) {
+        let value = track.sample(time).unwrap();
+        let d = value - v(x);
+        assert!(d.length() < 1e-9, "at {}: {:?} != {:?}", time, value, v(x));
+    }
+
+    #[test]
+    fn step() {
+        let track = make_track(
+            Channel::Translation,
+            Interpolation::Step,
+            &[(1.0, 1.0), (2.0, 3.0)],
+        );
+        assert_sample(&track, 0.0, 1.0);
+        assert_sample(&track, 1.0, 1.0);
+        assert_sample(&track, 1.99, 1.0);
+        assert_sample(&track, 2.0, 3.0);
+        assert_sample(&track, 5.0, 3.0);
+    }
+
+    #[test]
+    fn linear() {
+        let keys = [(0.0, 0.0), (2.0, 4.0), (3.0, 0.0)];
+        let track = make_track(Channel::Scale, Interpolation::Linear, &keys);
+        for &(time, x) in keys.iter() {
+            assert_sample(&track, time, x);
+        }
+        assert_sample(&track, 0.5, 1.0);
+        assert_sample(&track, 2.5, 2.0);
+        assert!(Track::new(0, Channel::Scale).sample(1.0).is_none());
+    }
+
+    #[test]
+    fn cubic() {
+        // a straight line stays straight
+        let keys = [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)];
+        let track = make_track(Channel::Translation, Interpolation::Cubic, &keys);
+        assert_sample(&track, 0.5, 0.5);
+        assert_sample(&track, 1.25, 1.25);
+
+        // goes through the keys, smooth over the peak
+        let keys = [(0.0, 0.0), (1.0, 1.0), (2.0, 0.0)];
+        let track = make_track(Channel::Translation, Interpolation::Cubic, &keys);
+        for &(time, x) in keys.iter() {
+            assert_sample(&track, time, x);
+        }
+        assert_sample(&track, 0.5, 0.625);
+        assert_sample(&track, 1.5, 0.625);
+    }
+
+    #[test]
+    fn rotation() {
+        let mut track = Track::new(0, Channel::Rotation);
+        track.insert(Keyframe::new(
+            0.0,
+            Vec3D::new(0.0, 0.0, 0.0),
+            Interpolation::Linear,
+        ));
+        track.insert(Keyframe::new(
+            1.0,
+            Vec3D::new(0.0, 1.0, 0.0),
+            Interpolation::Linear,
+        ));
+        // constant speed around a single axis
+        let half = track.sample(0.5).unwrap();
+        assert!(
+            (half - Vec3D::new(0.0, 0.5, 0.0)).length() < 1e-9,
+            "{:?}",
+            half
+        );
+    }
+
+    #[test]
+    fn insert_keeps_order() {
+        let mut track = make_track(
+            Channel::Translation,
+            Interpolation::Linear,
+            &[(2.0, 2.0), (0.0, 0.0)],
+        );
+        track.insert(Keyframe::new(1.0, v(5.0), Interpolation::Linear));
+        track.insert(Keyframe::new(1.0, v(1.0), Interpolation::Linear));
+        track.insert(Keyframe::new(f64::NAN, v(9.0), Interpolation::Linear));
+        let times: Vec<f64> = track.keys().iter().map(|key| key.time).collect();
+        assert_eq!(times, vec![0.0, 1.0, 2.0]);
+        assert_sample(&track, 1.0, 1.0);
+        assert_sample(&track, f64::NAN, 0.0);
+    }
+
+    fn timeline() -> Timeline {
+        let mut timeline = Timeline::new();
+        timeline.add_keyframe(
+            0,
+            Channel::Translation,
+            Keyframe::new(0.0, v(0.0), Interpolation::Linear),
+        );
+        timeline.add_keyframe(
+            0,
+            Channel::Translation,
+            Keyframe::new(2.0, v(4.0), Interpolation::Linear),
+        );
+        timeline
+    }
+
+    #[test]
+    fn looping() {
+        let mut timeline = timeline();
+        assert_eq!(timeline.duration(), 2.0);
+        timeline.seek(2.5);
+        assert_eq!(timeline.time(), 0.5);
+        timeline.seek(-0.5);
+        assert_eq!(timeline.time(), 1.5);
+
+        // the clock wraps the time around the end
+        timeline.seek(1.9);
+        timeline.advance(10.0);
+        timeline.advance(10.2);
+        assert!((timeline.time() - 0.1).abs() < 1e-9);
+
+        let mut graph = SceneGraph::new();
+        let node = graph.add_node(None, None);
+        timeline.apply(&mut graph);
+        let translation = graph.node(node).unwrap().transform.translation;
+        assert!((translation - v(0.2)).length() < 1e-9);
+    }
+
+    #[test]
+    fn clamped() {
+        let mut timeline = timeline();
+        timeline.looping = false;
+        timeline.seek(3.0);
+        assert_eq!(timeline.time(), 2.0);
+        timeline.seek(-1.0);
+        assert_eq!(timeline.time(), 0.0);
+    }
+
+    #[test]
+    fn advance() {
+        let mut timeline = timeline();
+        timeline.speed = 2.0;
+        // the first call only starts the clock
+        timeline.advance(100.0);
+        assert_eq!(timeline.time(), 0.0);
+        timeline.advance(100.1);
+        assert!((timeline.time() - 0.2).abs() < 1e-9);
+        // long pauses count as a single step, the clock going back as none
+        timeline.advance(200.0);
+        assert!((timeline.time() - 0.7).abs() < 1e-9);
+        timeline.advance(150.0);
+        assert!((timeline.time() - 0.7).abs() < 1e-9);
+
+        timeline.playing = false;
+        timeline.advance(150.1);
+        assert!((timeline.time() - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn non_finite_times() {
+        let mut timeline = timeline();
+        timeline.seek(1.0);
+        for time in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            timeline.seek(time);
+            timeline.advance(time);
+            assert_eq!(timeline.time(), 1.0);
+        }
+        // the clock picks up from the last valid value
+        timeline.advance(5.0);
+        timeline.advance(f64::NAN);
+        timeline.advance(5.1);
+        assert!((timeline.time() - 1.1).abs() < 1e-9);
+    }
+}
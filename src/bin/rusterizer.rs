@@ -0,0 +1,288 @@
+// Renders a model to a png without a browser:
+//   rusterizer model.stl -o thumbnail.png --size 256x256 --rotate 0.5,0.3,0
+// Run with --help for every option.
+
+extern crate Rustcaster;
+
+use std::{convert::TryInto, env, error::Error, fs, path::Path, process};
+
+use Rustcaster::light::{LightKind, ShadingMode};
+use Rustcaster::mesh::Mesh;
+use Rustcaster::obj::{self, MtlMaterial};
+use Rustcaster::projection::Projection;
+use Rustcaster::rasterizer::{CullMode, RenderMode, Winding};
+use Rustcaster::{framebuffer, image, loader, Scene};
+
+const USAGE: &str = "usage: rusterizer MODEL [options]
+
+MODEL is an .stl (ascii or binary) or .obj file. The materials of the .mtl
+files an .obj names with mtllib are read from next to it.
+The camera moves back until the whole model is in view, unless --camera,
+--look-at, --orbit, --translate or --scale place things by hand.
+
+options:
+  -o, --output FILE        png to write (default: out.png)
+  --size WxH               resolution in pixels, at most 16384 per side
+                           (default: 800x600)
+  --camera X,Y,Z           camera position
+  --look-at X,Y,Z          point the camera at
+  --orbit X,Y,Z,DISTANCE   orbit camera around a point
+  --fov DEGREES            perspective projection vertical field of view
+  --ortho HEIGHT           orthographic projection showing HEIGHT world units
+                           (HEIGHT is ignored when the model is framed)
+  --translate X,Y,Z        model position
+  --rotate X,Y,Z           model rotation in radians
+  --scale S                uniform model scale
+  --color R,G,B            model diffuse color in [0, 1], replaces the .mtl
+                           materials
+  --ambient R,G,B          ambient light
+  --light KIND:X,Y,Z[:R,G,B]
+                           adds a light, KIND is dir (X,Y,Z is the direction it
+                           shines to) or point (X,Y,Z is its position); the
+                           first one replaces the default light
+  --shading MODE           flat, gouraud or phong (default: flat)
+  --mode MODE              outlined, solid, wireframe, hidden-line or points
+                           (default: outlined)
+  --line-width PIXELS      edge width (default: 1)
+  --line-color R,G,B       edge and point color in [0, 1] (default: black)
+  --lines ALGORITHM        bresenham or wu (anti-aliased) (default: bresenham)
+  --cull MODE              back, front or none (default: back); none also lights
+                           both sides, for models with inconsistent winding
+  --winding ORDER          cw or ccw corner order of front faces (default: cw)
+  -h, --help               print this help";
+
+// comma separated floats, exactly N of them
+fn floats<const N: usize>(option: &str, value: &str) -> Result<[f64; N], String> {
+    let parsed: Vec<f64> = value
+        .split(',')
+        .map(|v| v.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("{}: invalid number in {:?}", option, value))?;
+    parsed
+        .try_into()
+        .map_err(|_| format!("{}: expected {} comma separated numbers", option, N))
+}
+
+// the mesh and, for an .obj, the materials of its libraries; a library that cannot
+// be read is only warned about
+fn load_model(path: &str) -> Result<(Mesh, Vec<MtlMaterial>), Box<dyn Error>> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("stl") => Ok((loader::load_stl(&bytes)?, Vec::new())),
+        Some("obj") => {
+            let mesh = obj::load_obj(&bytes)?;
+            let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+            let mut library = Vec::new();
+            for lib in &mesh.material_libs {
+                let lib = dir.join(lib);
+                match fs::read(&lib) {
+                    Ok(bytes) => library.extend(
+                        obj::load_mtl(&bytes).map_err(|e| format!("{}: {}", lib.display(), e))?,
+                    ),
+                    Err(e) => eprintln!("rusterizer: warning: {}: {}", lib.display(), e),
+                }
+            }
+            Ok((mesh, library))
+        }
+        _ => Err(format!("{}: unknown model format, expected .stl or .obj", path).into()),
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let mut model = None;
+    let mut output = "out.png".to_string();
+    let (mut width, mut height) = (800, 600);
+    // applied once the scene exists, in command line order
+    let mut options = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            if model.replace(arg).is_some() {
+                return Err("only one model can be rendered".into());
+            }
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{}: missing value", arg))?;
+        match arg.as_str() {
+            "-o" | "--output" => output = value,
+            "--size" => {
+                let size = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .filter(|&(w, h): &(u32, u32)| w > 0 && h > 0);
+                (width, height) = size.ok_or_else(|| format!("--size: invalid {:?}", value))?;
+                framebuffer::check_size(width, height).map_err(|e| format!("--size: {}", e))?;
+            }
+            _ => options.push((arg, value)),
+        }
+    }
+    let model = model.ok_or("missing MODEL")?;
+
+    let (mesh, library) = load_model(&model)?;
+    let mut scene = Scene::headless(width, height, mesh)?;
+    scene.link_materials(0, &library);
+    let mut default_light = true;
+    let mut framed = true;
+    for (option, value) in options {
+        let option = option.as_str();
+        if ["--camera", "--look-at", "--orbit", "--translate", "--scale"].contains(&option) {
+            framed = false;
+        }
+        match option {
+            "--camera" => {
+                let [x, y, z] = floats(option, &value)?;
+                scene.set_camera_position(x, y, z);
+            }
+            "--look-at" => {
+                let [x, y, z] = floats(option, &value)?;
+                scene.camera_look_at(x, y, z);
+            }
+            "--orbit" => {
+                let [x, y, z, distance] = floats(option, &value)?;
+                scene.camera_orbit(x, y, z, distance);
+            }
+            "--fov" => {
+                let [fov] = floats(option, &value)?;
+                let (near, far) = (scene.projection().near, scene.projection().far);
+                scene
+                    .set_projection(Projection::perspective(fov, near, far))
+                    .map_err(|e| format!("{}: {}", option, e))?;
+            }
+            "--ortho" => {
+                let [visible] = floats(option, &value)?;
+                let (near, far) = (scene.projection().near, scene.projection().far);
+                scene
+                    .set_projection(Projection::orthographic(visible, near, far))
+                    .map_err(|e| format!("{}: {}", option, e))?;
+            }
+            "--translate" => {
+                let [x, y, z] = floats(option, &value)?;
+                scene.set_translation(0, x, y, z);
+            }
+            "--rotate" => {
+                let [x, y, z] = floats(option, &value)?;
+                scene.set_rotation(0, x, y, z);
+            }
+            "--scale" => {
+                let [s] = floats(option, &value)?;
+                scene.set_scale(0, s, s, s);
+            }
+            "--color" => {
+                let [r, g, b] = floats(option, &value)?;
+                let material = scene.add_material();
+                scene.set_material_diffuse(material, r, g, b);
+                scene.set_node_material(0, Some(material));
+            }
+            "--ambient" => {
+                let [r, g, b] = floats(option, &value)?;
+                scene.set_ambient(r, g, b);
+            }
+            "--light" => {
+                let mut parts = value.split(':');
+                let kind = match parts.next() {
+                    Some("dir") => LightKind::Directional,
+                    Some("point") => LightKind::Point,
+                    _ => return Err(format!("--light: unknown kind in {:?}", value).into()),
+                };
+                let [x, y, z] = floats(option, parts.next().unwrap_or(""))?;
+                let [r, g, b] = match parts.next() {
+                    Some(color) => floats(option, color)?,
+                    None => [1.0; 3],
+                };
+                if default_light {
+                    scene.clear_lights();
+                    default_light = false;
+                }
+                let light = scene.add_light(kind);
+                match kind {
+                    LightKind::Directional => scene.set_light_direction(light, x, y, z),
+                    _ => scene.set_light_position(light, x, y, z),
+                }
+                scene.set_light_color(light, r, g, b, 1.0);
+            }
+            "--shading" => {
+                let shading = match value.as_str() {
+                    "flat" => ShadingMode::Flat,
+                    "gouraud" => ShadingMode::Gouraud,
+                    "phong" => ShadingMode::Phong,
+                    _ => return Err(format!("--shading: unknown mode {:?}", value).into()),
+                };
+                scene.set_shading(shading);
+            }
+            "--mode" => {
+                let mode = match value.as_str() {
+                    "outlined" => RenderMode::Outlined,
+                    "solid" => RenderMode::Solid,
+                    "wireframe" => RenderMode::Wireframe,
+                    "hidden-line" => RenderMode::HiddenLine,
+                    "points" => RenderMode::Points,
+                    _ => return Err(format!("--mode: unknown mode {:?}", value).into()),
+                };
+                scene.set_render_mode(mode);
+            }
+            "--line-width" => {
+                let [width] = floats(option, &value)?;
+                scene.set_line_width(width);
+            }
+            "--line-color" => {
+                let [r, g, b] = floats(option, &value)?;
+                scene.set_line_color(r, g, b, 1.0);
+            }
+            "--lines" => {
+                let antialiased = match value.as_str() {
+                    "bresenham" => false,
+                    "wu" => true,
+                    _ => return Err(format!("--lines: unknown algorithm {:?}", value).into()),
+                };
+                scene.set_line_antialiasing(antialiased);
+            }
+            "--cull" => {
+                let cull = match value.as_str() {
+                    "back" => CullMode::Back,
+                    "front" => CullMode::Front,
+                    "none" => CullMode::None,
+                    _ => return Err(format!("--cull: unknown mode {:?}", value).into()),
+                };
+                scene.set_cull_mode(cull);
+                scene.set_two_sided_lighting(cull == CullMode::None);
+            }
+            "--winding" => {
+                let winding = match value.as_str() {
+                    "cw" => Winding::Clockwise,
+                    "ccw" => Winding::CounterClockwise,
+                    _ => return Err(format!("--winding: unknown order {:?}", value).into()),
+                };
+                scene.set_front_face(winding);
+            }
+            _ => return Err(format!("unknown option {}", option).into()),
+        }
+    }
+
+    if framed && !scene.frame_node(0) {
+        return Err(format!("{}: the model has no vertices", model).into());
+    }
+
+    let png = image::encode_png(width, height, &scene.render_image(width, height)?)?;
+    fs::write(&output, png).map_err(|e| format!("{}: {}", output, e))?;
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run(env::args().skip(1).collect()) {
+        eprintln!("rusterizer: {}", e);
+        eprintln!("try --help");
+        process::exit(1);
+    }
+}
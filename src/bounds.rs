@@ -0,0 +1,236 @@
+use crate::transforms::Mat3D;
+use crate::vec3D::Vec3D;
+
+// Axis aligned bounding box
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3D,
+    pub max: Vec3D,
+}
+
+impl Aabb {
+    // None without any point
+    pub fn from_points<'a, I>(points: I) -> Option<Aabb>
+    where
+        I: IntoIterator<Item = &'a Vec3D>,
+    {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+        let (mut min, mut max) = (first, first);
+        for p in points {
+            min = Vec3D::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Vec3D::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+        Some(Aabb { min, max })
+    }
+
+    pub fn center(&self) -> Vec3D {
+        (self.min + self.max).scale(0.5)
+    }
+
+    pub fn corners(&self) -> [Vec3D; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3D::new(a.x, a.y, a.z),
+            Vec3D::new(b.x, a.y, a.z),
+            Vec3D::new(a.x, b.y, a.z),
+            Vec3D::new(b.x, b.y, a.z),
+            Vec3D::new(a.x, a.y, b.z),
+            Vec3D::new(b.x, a.y, b.z),
+            Vec3D::new(a.x, b.y, b.z),
+            Vec3D::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // box around the transformed corners, a bit larger than the box when it rotates
+    pub fn transformed(&self, matrix: &Mat3D) -> Aabb {
+        let corners = self.corners().map(|corner| corner.mul(matrix));
+        Aabb::from_points(corners.iter()).expect("a box has corners")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sphere {
+    pub center: Vec3D,
+    pub radius: f64,
+}
+
+impl Sphere {
+    // centered on the box of the points, not the smallest sphere but close enough
+    pub fn from_points<'a, I>(points: I, aabb: &Aabb) -> Sphere
+    where
+        I: IntoIterator<Item = &'a Vec3D>,
+    {
+        let center = aabb.center();
+        let radius = points
+            .into_iter()
+            .map(|p| (*p - center).length())
+            .fold(0.0, f64::max);
+        Sphere { center, radius }
+    }
+
+    // the radius grows with the largest scale of the matrix
+    pub fn transformed(&self, matrix: &Mat3D) -> Sphere {
+        let scale = (0..3u8)
+            .map(|i| Vec3D::new(matrix[i][0], matrix[i][1], matrix[i][2]).length())
+            .fold(0.0, f64::max);
+        Sphere {
+            center: self.center.mul(matrix),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+// Both volumes of a mesh: the sphere is the cheap test, the box the tight one
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: Sphere,
+}
+
+impl Bounds {
+    pub fn from_points<'a, I>(points: I) -> Option<Bounds>
+    where
+        I: IntoIterator<Item = &'a Vec3D> + Clone,
+    {
+        let aabb = Aabb::from_points(points.clone())?;
+        let sphere = Sphere::from_points(points, &aabb);
+        Some(Bounds { aabb, sphere })
+    }
+
+    pub fn transformed(&self, matrix: &Mat3D) -> Bounds {
+        Bounds {
+            aabb: self.aabb.transformed(matrix),
+            sphere: self.sphere.transformed(matrix),
+        }
+    }
+}
+
+// The six planes of the camera view volume, in the space of the points tested
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    // a, b, c, d with a * x + b * y + c * z + d >= 0 inside and (a, b, c) normalized
+    planes: [[f64; 4]; 6],
+}
+
+impl Frustum {
+    // Planes of a view * projection matrix, in world space: the clip space inequalities
+    // -w <= x <= w, -w <= y <= w and 0 <= z <= w written with the matrix columns
+    pub fn from_matrix(m: &Mat3D) -> Frustum {
+        let col = |j: usize| [m[0][j], m[1][j], m[2][j], m[3][j]];
+        let (x, y, z, w) = (col(0), col(1), col(2), col(3));
+        let add = |a: [f64; 4], b: [f64; 4], sign: f64| [0, 1, 2, 3].map(|i| a[i] + sign * b[i]);
+        let planes = [
+            add(w, x, 1.0),
+            add(w, x, -1.0),
+            add(w, y, 1.0),
+            add(w, y, -1.0),
+            z,
+            add(w, z, -1.0),
+        ]
+        .map(|p| {
+            let length = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+            if length > 0.0 {
+                p.map(|v| v / length)
+            } else {
+                p
+            }
+        });
+        Frustum { planes }
+    }
+
+    fn distance(plane: &[f64; 4], p: &Vec3D) -> f64 {
+        plane[0] * p.x + plane[1] * p.y + plane[2] * p.z + plane[3]
+    }
+
+    // false when the sphere is fully outside one plane
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Frustum::distance(plane, &sphere.center) >= -sphere.radius)
+    }
+
+    // false when the box is fully outside one plane: tests the corner furthest along
+    // the plane normal. Boxes near the frustum corners may pass without being visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let corner = Vec3D::new(
+                if plane[0] >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane[1] >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane[2] >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            Frustum::distance(plane, &corner) >= 0.0
+        })
+    }
+
+    pub fn intersects(&self, bounds: &Bounds) -> bool {
+        self.intersects_sphere(&bounds.sphere) && self.intersects_aabb(&bounds.aabb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // unit cube around a point
+    fn cube(x: f64, y: f64, z: f64) -> Bounds {
+        let aabb = Aabb {
+            min: Vec3D::new(x - 0.5, y - 0.5, z - 0.5),
+            max: Vec3D::new(x + 0.5, y + 0.5, z + 0.5),
+        };
+        Bounds::from_points(aabb.corners().iter()).unwrap()
+    }
+
+    // camera at the origin looking down +z, 90 degrees field of view
+    fn frustum() -> Frustum {
+        Frustum::from_matrix(&Mat3D::projection(90.0, 1.0, 0.1, 100.0))
+    }
+
+    #[test]
+    fn inside() {
+        assert!(frustum().intersects(&cube(0.0, 0.0, 5.0)));
+        assert!(frustum().intersects(&cube(3.0, -3.0, 10.0)));
+    }
+
+    #[test]
+    fn behind_and_beyond() {
+        assert!(!frustum().intersects(&cube(0.0, 0.0, -5.0)));
+        assert!(!frustum().intersects(&cube(0.0, 0.0, 150.0)));
+        // across the near plane or the far one, partly in view
+        assert!(frustum().intersects(&cube(0.0, 0.0, 0.0)));
+        assert!(frustum().intersects(&cube(0.0, 0.0, 100.2)));
+    }
+
+    #[test]
+    fn sides() {
+        // the side planes go through x = +-z and y = +-z
+        assert!(frustum().intersects(&cube(5.0, 0.0, 5.0)));
+        assert!(frustum().intersects(&cube(0.0, -5.3, 5.0)));
+        assert!(!frustum().intersects(&cube(7.0, 0.0, 5.0)));
+        assert!(!frustum().intersects(&cube(0.0, -7.0, 5.0)));
+    }
+
+    #[test]
+    fn transformed() {
+        let bounds = cube(0.0, 0.0, 0.0);
+        let matrix = Mat3D::scale(2.0, 2.0, 2.0) * Mat3D::translation(0.0, 0.0, -5.0);
+        assert!(!frustum().intersects(&bounds.transformed(&matrix)));
+        let matrix = Mat3D::scale(2.0, 2.0, 2.0) * Mat3D::translation(0.0, 0.0, 5.0);
+        let moved = bounds.transformed(&matrix);
+        assert!(frustum().intersects(&moved));
+        assert!((moved.sphere.radius - 3f64.sqrt()).abs() < 1e-9);
+        assert!((moved.aabb.max.z - 6.0).abs() < 1e-9);
+    }
+}
@@ -1,34 +1,170 @@
-use crate::transforms::Mat3D;
+use std::f64::consts::FRAC_PI_2;
+
+use wasm_bindgen::prelude::*;
+
+use crate::transforms::{Mat3D, Quat};
 use crate::tri3D::Tri3D;
 use crate::vec3D::Vec3D;
 
+// keeps first person and orbit cameras from flipping over the poles
+const MAX_PITCH: f64 = FRAC_PI_2 - 0.001;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    // yaw, pitch and roll around the up vector, pitch limited to straight up / down
+    FirstPerson = 0,
+    // same angles, but the position turns around `target` at `distance`
+    Orbit = 1,
+    // orientation is only changed by `rotate` increments, no angle limit
+    Free = 2,
+}
+
 pub struct Camera {
-    pub yaw: f64,
+    pub mode: CameraMode,
     pub pos: Vec3D,
+    // radians, yaw > 0 turns towards -x, pitch > 0 looks up, roll turns around the view axis
+    pub yaw: f64,
+    pub pitch: f64,
+    pub roll: f64,
+    // world up, the camera forward is +z when it is +y and all angles are 0
+    pub up: Vec3D,
+    // orbit center and radius
+    pub target: Vec3D,
+    pub distance: f64,
+    pub orientation: Quat,
     pub look_dir: Vec3D,
     view_mat: Mat3D,
 }
 
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Camera {
     pub fn new() -> Camera {
         Camera {
+            mode: CameraMode::FirstPerson,
             pos: Vec3D::new(0.0, 0.0, 0.0),
-            look_dir: Vec3D::new(1.0, 0.0, 0.0),
             yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            up: Vec3D::new(0.0, 1.0, 0.0),
+            target: Vec3D::new(0.0, 0.0, 0.0),
+            distance: 1.0,
+            orientation: Quat::identity(),
+            look_dir: Vec3D::new(0.0, 0.0, 1.0),
             view_mat: Mat3D::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
         }
     }
 
+    // forward, up and right of the camera before any rotation
+    fn rest_frame(&self) -> (Vec3D, Vec3D, Vec3D) {
+        let up = self.up.normalized();
+        let mut reference = Vec3D::new(0.0, 0.0, 1.0);
+        if up.cross_product(reference).length() < 1e-6 {
+            reference = Vec3D::new(-1.0, 0.0, 0.0);
+        }
+        let forward = (reference - up.scale(up.dot_product(reference))).normalized();
+        let right = up.cross_product(forward);
+        (forward, up, right)
+    }
+
+    // rotation around the camera own axes
+    fn local_rotation(&self, yaw: f64, pitch: f64, roll: f64) -> Quat {
+        let (forward, up, _) = self.rest_frame();
+        Quat::from_axis_angle(&up, -yaw)
+            * Quat::from_axis_angle(&forward.cross_product(up), pitch)
+            * Quat::from_axis_angle(&forward, roll)
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if self.mode == CameraMode::Free && mode != CameraMode::Free {
+            // back to angles: keep looking the same way, roll is lost
+            let target = self.pos + self.look_dir;
+            self.look_at(&target);
+            self.roll = 0.0;
+        }
+        if mode == CameraMode::Orbit {
+            self.distance = (self.target - self.pos).length().max(1e-3);
+        }
+        self.mode = mode;
+    }
+
+    // yaw, pitch and roll increments in radians, relative to the current orientation
+    pub fn rotate(&mut self, yaw: f64, pitch: f64, roll: f64) {
+        match self.mode {
+            CameraMode::Free => {
+                self.orientation =
+                    (self.orientation * self.local_rotation(yaw, pitch, roll)).normalized();
+            }
+            _ => {
+                self.yaw += yaw;
+                self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+                self.roll += roll;
+            }
+        }
+    }
+
+    // turn towards a point, in orbit mode it becomes the orbit center instead
+    pub fn look_at(&mut self, target: &Vec3D) {
+        if self.mode == CameraMode::Orbit {
+            self.target = *target;
+            self.distance = (*target - self.pos).length().max(1e-3);
+        }
+        let dir = *target - self.pos;
+        if dir.length() < 1e-9 {
+            return;
+        }
+        let dir = dir.normalized();
+        let (forward, up, right) = self.rest_frame();
+
+        self.yaw = -dir.dot_product(right).atan2(dir.dot_product(forward));
+        self.pitch = dir.dot_product(up).clamp(-1.0, 1.0).asin();
+        if self.mode != CameraMode::Free {
+            self.pitch = self.pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        self.orientation = self.local_rotation(self.yaw, self.pitch, self.roll);
+    }
+
+    // orbit around `target` from `distance` away
+    pub fn orbit(&mut self, target: &Vec3D, distance: f64) {
+        self.mode = CameraMode::Orbit;
+        self.target = *target;
+        self.distance = distance.max(1e-3);
+    }
+
     pub fn compute_view(&mut self) {
-        // yaw -> 0,0,1 * rotY -> lookDir -> target -> (matCamera)-1 -> view_mat
-        let camera_rotation_y = Mat3D::rot_y(self.yaw);
-        self.look_dir = Vec3D::new(0.0, 0.0, 1.0).mul(&camera_rotation_y);
+        // angles -> orientation -> lookDir -> target -> (matCamera)-1 -> view_mat
+        if self.mode != CameraMode::Free {
+            self.orientation = self.local_rotation(self.yaw, self.pitch, self.roll);
+        }
+        let (forward, up, _) = self.rest_frame();
+        self.look_dir = self.orientation.rotate(&forward);
+        if self.mode == CameraMode::Orbit {
+            self.pos = self.target - self.look_dir.scale(self.distance);
+        }
 
         let target = self.pos + self.look_dir;
-        let camera_mat = Mat3D::point_at(&self.pos, &target, &Vec3D::new(0.0, 1.0, 0.0));
+        let camera_up = self.orientation.rotate(&up);
+        let camera_mat = Mat3D::point_at(&self.pos, &target, &camera_up);
         self.view_mat = camera_mat.quick_inverse();
     }
 
+    // look direction, right and up of the camera as of the last compute_view
+    pub fn axes(&self) -> (Vec3D, Vec3D, Vec3D) {
+        let up = self.orientation.rotate(&self.up.normalized());
+        let right = up.cross_product(self.look_dir).normalized();
+        (self.look_dir, right, up)
+    }
+
+    // world to view space, as of the last compute_view
+    pub fn view_matrix(&self) -> &Mat3D {
+        &self.view_mat
+    }
+
     pub fn to_view(&self, other: Tri3D) -> Tri3D {
         other.mul(&self.view_mat)
     }
@@ -0,0 +1,271 @@
+use crate::tri3D::Tri3D;
+use crate::vec3D::Vec3D;
+use crate::vertex::VertexAttr;
+
+// Sides of the view frustum in clip space, after the projection and before the divide
+// by w. Inside is -w <= x <= w, -w <= y <= w and 0 <= z <= w.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Plane {
+    Near,
+    Far,
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl Plane {
+    // near first: every later plane then only sees points with w > 0
+    pub const ALL: [Plane; 6] = [
+        Plane::Near,
+        Plane::Far,
+        Plane::Left,
+        Plane::Right,
+        Plane::Bottom,
+        Plane::Top,
+    ];
+
+    // >= 0 inside. The side planes are pushed `guard_band` times further out
+    pub fn distance(self, p: &Vec3D, guard_band: f64) -> f64 {
+        match self {
+            Plane::Near => p.z,
+            Plane::Far => p.w - p.z,
+            Plane::Left => guard_band * p.w + p.x,
+            Plane::Right => guard_band * p.w - p.x,
+            Plane::Bottom => guard_band * p.w + p.y,
+            Plane::Top => guard_band * p.w - p.y,
+        }
+    }
+}
+
+// polygon corner while clipping
+#[derive(Debug, Clone, Copy)]
+struct Corner {
+    pos: Vec3D,
+    attr: VertexAttr,
+}
+
+// everything is linear in clip space, w included
+fn lerp(a: &Vec3D, b: &Vec3D, t: f64) -> Vec3D {
+    Vec3D {
+        x: a.x + (b.x - a.x) * t,
+        y: a.y + (b.y - a.y) * t,
+        z: a.z + (b.z - a.z) * t,
+        w: a.w + (b.w - a.w) * t,
+    }
+}
+
+impl Corner {
+    fn lerp(&self, other: &Corner, t: f64) -> Corner {
+        Corner {
+            pos: lerp(&self.pos, &other.pos, t),
+            attr: self.attr.lerp(&other.attr, t),
+        }
+    }
+}
+
+// Sutherland-Hodgman: keeps the part of the convex polygon inside the plane
+fn clip_polygon(polygon: &[Corner], plane: Plane, guard_band: f64) -> Vec<Corner> {
+    let mut res = Vec::with_capacity(polygon.len() + 1);
+    for (i, current) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        let d0 = plane.distance(&current.pos, guard_band);
+        let d1 = plane.distance(&next.pos, guard_band);
+        if d0 >= 0.0 {
+            res.push(*current);
+        }
+        if (d0 >= 0.0) != (d1 >= 0.0) {
+            res.push(current.lerp(next, d0 / (d0 - d1)));
+        }
+    }
+    res
+}
+
+// Clips a clip space triangle to the frustum and pushes what is left to `out`: nothing,
+// the triangle itself or a fan of triangles sharing its material and color.
+// With a guard band > 1 triangles crossing the screen edges are kept whole, the
+// rasterizer skips the pixels outside the viewport anyway; they are only cut when
+// they reach that far, to keep the screen coordinates small.
+pub fn clip_tri(tri: &Tri3D, guard_band: f64, out: &mut Vec<Tri3D>) {
+    let corners = [tri[0], tri[1], tri[2]];
+    let mut crossed = Vec::new();
+    for plane in Plane::ALL.iter() {
+        let inside = corners
+            .iter()
+            .filter(|p| plane.distance(p, guard_band) >= 0.0)
+            .count();
+        match inside {
+            0 => return,
+            3 => continue,
+            _ => crossed.push(*plane),
+        }
+    }
+    if crossed.is_empty() {
+        out.push(*tri);
+        return;
+    }
+
+    let mut polygon: Vec<Corner> = (0..3)
+        .map(|i| Corner {
+            pos: corners[i],
+            attr: tri.attrs[i],
+        })
+        .collect();
+    for plane in crossed {
+        polygon = clip_polygon(&polygon, plane, guard_band);
+        if polygon.len() < 3 {
+            return;
+        }
+    }
+    for i in 1..polygon.len() - 1 {
+        let (a, b, c) = (polygon[0], polygon[i], polygon[i + 1]);
+        out.push(Tri3D {
+            a: a.pos,
+            b: b.pos,
+            c: c.pos,
+            attrs: [a.attr, b.attr, c.attr],
+            ..*tri
+        });
+    }
+}
+
+// true when the clip space point is inside the frustum itself, without guard band
+pub fn contains(p: &Vec3D) -> bool {
+    Plane::ALL.iter().all(|plane| plane.distance(p, 1.0) >= 0.0)
+}
+
+// Part of the clip space segment a -> b inside the frustum, None when it is all outside.
+// Like clip_tri the side planes are pushed out by the guard band
+pub fn clip_segment(a: &Vec3D, b: &Vec3D, guard_band: f64) -> Option<[Vec3D; 2]> {
+    // the kept part is [t0, t1] along the segment
+    let (mut t0, mut t1) = (0.0, 1.0);
+    for plane in Plane::ALL.iter() {
+        let d0 = plane.distance(a, guard_band);
+        let d1 = plane.distance(b, guard_band);
+        match (d0 >= 0.0, d1 >= 0.0) {
+            (false, false) => return None,
+            (true, true) => {}
+            (false, true) => t0 = f64::max(t0, d0 / (d0 - d1)),
+            (true, false) => t1 = f64::min(t1, d0 / (d0 - d1)),
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some([lerp(a, b, t0), lerp(a, b, t1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec2D::Vec2D;
+
+    // clip space triangle, each corner uv set to its x and y to check the interpolation
+    fn tri(corners: [[f64; 3]; 3]) -> Tri3D {
+        let [a, b, c] = corners.map(|[x, y, z]| Vec3D::new(x, y, z));
+        let attr = |p: &Vec3D| VertexAttr::new(Vec3D::new(0.0, 0.0, 0.0), Vec2D::new(p.x, p.y));
+        Tri3D::new_with_attrs(a, b, c, [attr(&a), attr(&b), attr(&c)])
+    }
+
+    fn clip(tri: &Tri3D, guard_band: f64) -> Vec<Tri3D> {
+        let mut out = Vec::new();
+        clip_tri(tri, guard_band, &mut out);
+        for tri in out.iter() {
+            for i in 0..3u8 {
+                assert!(
+                    Plane::ALL
+                        .iter()
+                        .all(|plane| plane.distance(&tri[i], guard_band) >= -1e-12),
+                    "{:?} is outside",
+                    tri[i]
+                );
+                let uv = tri.attrs[i as usize].uv;
+                assert!((uv.x - tri[i].x).abs() < 1e-12 && (uv.y - tri[i].y).abs() < 1e-12);
+            }
+        }
+        out
+    }
+
+    const INSIDE: [[f64; 3]; 3] = [[0.0, 0.0, 0.5], [0.5, 0.0, 0.5], [0.0, 0.5, 0.5]];
+
+    #[test]
+    fn inside() {
+        let out = clip(&tri(INSIDE), 1.0);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0][2u8].y, 0.5);
+    }
+
+    #[test]
+    fn outside() {
+        let out = clip(
+            &tri([[1.5, 0.0, 0.5], [2.0, 0.0, 0.5], [1.5, 0.5, 0.5]]),
+            1.0,
+        );
+        assert!(out.is_empty());
+        // behind the camera
+        let out = clip(
+            &tri([[0.0, 0.0, -0.5], [0.5, 0.0, -1.0], [0.0, 0.5, -0.1]]),
+            1.0,
+        );
+        assert!(out.is_empty());
+    }
+
+    // the last corner moved out through `plane` only: the quad left is split in two
+    fn one_corner_out(plane: Plane, corner: [f64; 3]) {
+        let mut corners = INSIDE;
+        corners[2] = corner;
+        let tri = tri(corners);
+        assert!(plane.distance(&tri[2u8], 1.0) < 0.0);
+        assert_eq!(clip(&tri, 1.0).len(), 2, "{:?}", plane);
+    }
+
+    #[test]
+    fn near() {
+        one_corner_out(Plane::Near, [0.0, 0.5, -0.5]);
+    }
+
+    #[test]
+    fn far() {
+        one_corner_out(Plane::Far, [0.0, 0.5, 1.5]);
+    }
+
+    #[test]
+    fn left() {
+        one_corner_out(Plane::Left, [-2.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn right() {
+        one_corner_out(Plane::Right, [2.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn bottom() {
+        one_corner_out(Plane::Bottom, [0.0, -2.0, 0.5]);
+    }
+
+    #[test]
+    fn top() {
+        one_corner_out(Plane::Top, [0.0, 2.0, 0.5]);
+    }
+
+    #[test]
+    fn guard_band() {
+        // past the right edge but within the guard band: kept whole
+        let tri = tri([[0.0, 0.0, 0.5], [1.5, 0.0, 0.5], [0.0, 0.5, 0.5]]);
+        assert_eq!(clip(&tri, 2.0).len(), 1);
+        assert_eq!(clip(&tri, 1.0).len(), 2);
+    }
+
+    #[test]
+    fn segment() {
+        let (a, b) = (Vec3D::new(-2.0, 0.0, 0.5), Vec3D::new(2.0, 0.0, 0.5));
+        let [a, b] = clip_segment(&a, &b, 1.0).unwrap();
+        assert_eq!((a.x, b.x), (-1.0, 1.0));
+        assert!(contains(&a) && contains(&b));
+
+        // crosses the left and top planes, but out of the corner between them
+        let (a, b) = (Vec3D::new(-3.0, 0.0, 0.5), Vec3D::new(0.0, 3.0, 0.5));
+        assert!(clip_segment(&a, &b, 1.0).is_none());
+    }
+}
@@ -0,0 +1,313 @@
+use std::{error, fmt};
+
+// RGBA, 8 bits per channel
+pub type Color = [u8; 4];
+
+pub const BLACK: Color = [0, 0, 0, 255];
+pub const TRANSPARENT: Color = [0, 0, 0, 0];
+
+// Pixel rectangle, top left origin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // overlap of both rectangles, empty when they do not touch
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        Rect::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+    }
+
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        x >= self.x as i64
+            && y >= self.y as i64
+            && x < (self.x + self.width) as i64
+            && y < (self.y + self.height) as i64
+    }
+}
+
+// Region of the render target as fractions of its size, so it follows resizes.
+// (0, 0, 1, 1) is the whole target, (0.5, 0, 0.5, 1) its right half.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport::new(0.0, 0.0, 1.0, 1.0)
+    }
+}
+
+impl Viewport {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Viewport {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // pixels covered in a target of this size
+    pub fn to_rect(&self, width: u32, height: u32) -> Rect {
+        let (w, h) = (width as f64, height as f64);
+        let x0 = (self.x.clamp(0.0, 1.0) * w).round();
+        let y0 = (self.y.clamp(0.0, 1.0) * h).round();
+        let x1 = ((self.x + self.width).clamp(0.0, 1.0) * w).round();
+        let y1 = ((self.y + self.height).clamp(0.0, 1.0) * h).round();
+        Rect::new(
+            x0 as u32,
+            y0 as u32,
+            (x1 - x0).max(0.0) as u32,
+            (y1 - y0).max(0.0) as u32,
+        )
+    }
+}
+
+// Largest width or height: 16384 x 16384 already takes 1 GiB of color and 2 GiB of
+// depth, and keeps every pixel index within a u32
+pub const MAX_SIZE: u32 = 16384;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FramebufferError {
+    // larger than MAX_SIZE
+    Size { width: u32, height: u32 },
+    // the allocator refused the buffers
+    OutOfMemory { width: u32, height: u32 },
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FramebufferError::Size { width, height } => write!(
+                f,
+                "framebuffer: {}x{} is too large, at most {} pixels per side",
+                width, height, MAX_SIZE
+            ),
+            FramebufferError::OutOfMemory { width, height } => {
+                write!(f, "framebuffer: out of memory for {}x{}", width, height)
+            }
+        }
+    }
+}
+
+impl error::Error for FramebufferError {}
+
+// number of pixels of a framebuffer of this size, if it may have one
+pub fn check_size(width: u32, height: u32) -> Result<usize, FramebufferError> {
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(FramebufferError::Size { width, height });
+    }
+    (width as usize)
+        .checked_mul(height as usize)
+        .ok_or(FramebufferError::Size { width, height })
+}
+
+// `len` copies of `value`, without aborting when the memory is not there
+fn alloc<T: Clone>(
+    len: usize,
+    value: T,
+    width: u32,
+    height: u32,
+) -> Result<Vec<T>, FramebufferError> {
+    let mut buffer = Vec::new();
+    buffer
+        .try_reserve_exact(len)
+        .map_err(|_| FramebufferError::OutOfMemory { width, height })?;
+    buffer.resize(len, value);
+    Ok(buffer)
+}
+
+// Software render target: a plain RGBA buffer, row major, top left origin,
+// with one depth value per pixel for the rasterizer.
+// Clearing and drawing only touch pixels inside the scissor rectangle.
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    color: Vec<u8>,
+    depth: Vec<f64>,
+    scissor: Rect,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Result<Framebuffer, FramebufferError> {
+        let (color, depth) = Framebuffer::buffers(width, height)?;
+        Ok(Framebuffer {
+            width,
+            height,
+            color,
+            depth,
+            scissor: Rect::new(0, 0, width, height),
+        })
+    }
+
+    // cleared color and depth buffers of that size
+    fn buffers(width: u32, height: u32) -> Result<(Vec<u8>, Vec<f64>), FramebufferError> {
+        let pixels = check_size(width, height)?;
+        let bytes = pixels
+            .checked_mul(4)
+            .ok_or(FramebufferError::Size { width, height })?;
+        Ok((
+            alloc(bytes, 0, width, height)?,
+            alloc(pixels, f64::INFINITY, width, height)?,
+        ))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // raw RGBA bytes, width * height * 4
+    pub fn pixels(&self) -> &[u8] {
+        &self.color
+    }
+
+    // also resets the scissor to the whole buffer. Left untouched on error
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        if self.width == width && self.height == height {
+            return Ok(());
+        }
+        let (color, depth) = Framebuffer::buffers(width, height)?;
+        self.width = width;
+        self.height = height;
+        self.color = color;
+        self.depth = depth;
+        self.scissor = Rect::new(0, 0, width, height);
+        Ok(())
+    }
+
+    pub fn scissor(&self) -> Rect {
+        self.scissor
+    }
+
+    // None for the whole buffer
+    pub fn set_scissor(&mut self, scissor: Option<Rect>) {
+        let full = Rect::new(0, 0, self.width, self.height);
+        self.scissor = match scissor {
+            Some(rect) => rect.intersect(&full),
+            None => full,
+        };
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        let Rect { x, y, width, .. } = self.scissor;
+        for row in y..y + self.scissor.height {
+            let start = ((row * self.width + x) * 4) as usize;
+            let end = start + (width * 4) as usize;
+            for pixel in self.color[start..end].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+
+    // everything is infinitely far away
+    pub fn clear_depth(&mut self) {
+        let Rect { x, y, width, .. } = self.scissor;
+        for row in y..y + self.scissor.height {
+            let start = (row * self.width + x) as usize;
+            for depth in self.depth[start..start + width as usize].iter_mut() {
+                *depth = f64::INFINITY;
+            }
+        }
+    }
+
+    pub fn depth(&self, x: u32, y: u32) -> f64 {
+        self.depth[(y * self.width + x) as usize]
+    }
+
+    pub fn set_depth(&mut self, x: u32, y: u32, z: f64) {
+        self.depth[(y * self.width + x) as usize] = z;
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        let i = ((y * self.width + x) * 4) as usize;
+        [
+            self.color[i],
+            self.color[i + 1],
+            self.color[i + 2],
+            self.color[i + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: i64, y: i64, color: Color) {
+        if !self.scissor.contains(x, y) {
+            return;
+        }
+        let i = ((y as u32 * self.width + x as u32) * 4) as usize;
+        self.color[i..i + 4].copy_from_slice(&color);
+    }
+
+    // Draws `color` over the current pixel according to its alpha ("source over").
+    // Colors are not premultiplied: each side weighs by its own alpha, the pixel keeps
+    // the covered part, and the sum is divided by it so a translucent color over an
+    // empty pixel keeps its full strength.
+    pub fn blend_pixel(&mut self, x: i64, y: i64, color: Color) {
+        if !self.scissor.contains(x, y) {
+            return;
+        }
+        let i = ((y as u32 * self.width + x as u32) * 4) as usize;
+        // weights out of 255 * 255
+        let src_weight = color[3] as u32 * 255;
+        let dst_weight = self.color[i + 3] as u32 * (255 - color[3] as u32);
+        let total = src_weight + dst_weight;
+        if total == 0 {
+            return;
+        }
+        for (dst, src) in self.color[i..i + 3].iter_mut().zip(color.iter()) {
+            *dst =
+                ((*src as u32 * src_weight + *dst as u32 * dst_weight + total / 2) / total) as u8;
+        }
+        self.color[i + 3] = ((total + 127) / 255) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_over_empty() {
+        let mut framebuffer = Framebuffer::new(1, 1).unwrap();
+        framebuffer.clear(TRANSPARENT);
+        framebuffer.blend_pixel(0, 0, [255, 0, 0, 128]);
+        // the color keeps its strength, only the coverage is partial
+        assert_eq!(framebuffer.get_pixel(0, 0), [255, 0, 0, 128]);
+        framebuffer.blend_pixel(0, 0, [255, 0, 0, 128]);
+        assert_eq!(framebuffer.get_pixel(0, 0), [255, 0, 0, 192]);
+    }
+
+    #[test]
+    fn blend_over_opaque() {
+        let mut framebuffer = Framebuffer::new(1, 1).unwrap();
+        framebuffer.clear([255, 255, 255, 255]);
+        framebuffer.blend_pixel(0, 0, [255, 0, 0, 128]);
+        assert_eq!(framebuffer.get_pixel(0, 0), [255, 127, 127, 255]);
+        // a fully transparent color changes nothing
+        framebuffer.blend_pixel(0, 0, [0, 0, 0, 0]);
+        assert_eq!(framebuffer.get_pixel(0, 0), [255, 127, 127, 255]);
+    }
+}
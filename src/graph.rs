@@ -0,0 +1,291 @@
+use crate::bounds::{Aabb, Bounds};
+use crate::material::Material;
+use crate::mesh::Mesh;
+use crate::texture::Texture;
+use crate::transforms::Mat3D;
+use crate::vec3D::Vec3D;
+
+// Local placement of a node relative to its parent.
+// Applied as scale, then rotation around x, y and z (radians), then translation.
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub translation: Vec3D,
+    pub rotation: Vec3D,
+    pub scale: Vec3D,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: Vec3D::new(0.0, 0.0, 0.0),
+            rotation: Vec3D::new(0.0, 0.0, 0.0),
+            scale: Vec3D::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat3D {
+        Mat3D::scale(self.scale.x, self.scale.y, self.scale.z)
+            * Mat3D::rot_x(self.rotation.x)
+            * Mat3D::rot_y(self.rotation.y)
+            * Mat3D::rot_z(self.rotation.z)
+            * Mat3D::translation(self.translation.x, self.translation.y, self.translation.z)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    // index in SceneGraph meshes, None for a pure grouping node
+    pub mesh: Option<usize>,
+    // index in SceneGraph materials, overrides the mesh material, children without
+    // a material of their own or on their mesh inherit it
+    pub material: Option<usize>,
+    pub transform: Transform,
+    pub children: Vec<usize>,
+    pub parent: Option<usize>,
+    // hides the node and all its children
+    pub visible: bool,
+}
+
+// Nodes live in an arena and refer to each other by index, ids stay valid for the
+// lifetime of the graph (removing a node only detaches it).
+// Meshes, materials and textures are stored once and can be shared by several nodes.
+#[derive(Debug, Clone, Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+    meshes: Vec<Mesh>,
+    // in mesh space, None for a mesh without vertices
+    mesh_bounds: Vec<Option<Bounds>>,
+    // material of each mesh, when it has one
+    mesh_materials: Vec<Option<usize>>,
+    materials: Vec<Material>,
+    textures: Vec<Texture>,
+}
+
+impl SceneGraph {
+    pub fn new() -> SceneGraph {
+        SceneGraph::default()
+    }
+
+    pub fn add_mesh(&mut self, mesh: Mesh) -> usize {
+        self.mesh_bounds
+            .push(Bounds::from_points(mesh.vertices.iter().map(|v| &v.pos)));
+        self.meshes.push(mesh);
+        self.mesh_materials.push(None);
+        self.meshes.len() - 1
+    }
+
+    pub fn mesh(&self, id: usize) -> Option<&Mesh> {
+        self.meshes.get(id)
+    }
+
+    pub fn mesh_bounds(&self, id: usize) -> Option<&Bounds> {
+        self.mesh_bounds.get(id).and_then(Option::as_ref)
+    }
+
+    // material used by every node showing the mesh, unless the node overrides it
+    pub fn set_mesh_material(&mut self, mesh: usize, material: Option<usize>) {
+        if let Some(slot) = self.mesh_materials.get_mut(mesh) {
+            *slot = material;
+        }
+    }
+
+    // material of a run of triangles of the mesh, in place of the mesh material
+    pub fn set_group_material(&mut self, mesh: usize, group: usize, material: Option<usize>) {
+        if let Some(group) = self
+            .meshes
+            .get_mut(mesh)
+            .and_then(|mesh| mesh.groups.get_mut(group))
+        {
+            group.material_id = material;
+        }
+    }
+
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    pub fn material(&self, id: usize) -> Option<&Material> {
+        self.materials.get(id)
+    }
+
+    pub fn material_mut(&mut self, id: usize) -> Option<&mut Material> {
+        self.materials.get_mut(id)
+    }
+
+    pub fn add_texture(&mut self, texture: Texture) -> usize {
+        self.textures.push(texture);
+        self.textures.len() - 1
+    }
+
+    pub fn texture(&self, id: usize) -> Option<&Texture> {
+        self.textures.get(id)
+    }
+
+    pub fn texture_mut(&mut self, id: usize) -> Option<&mut Texture> {
+        self.textures.get_mut(id)
+    }
+
+    // new node with an identity transform, at the root when parent is None
+    pub fn add_node(&mut self, parent: Option<usize>, mesh: Option<usize>) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            mesh,
+            material: None,
+            transform: Transform::default(),
+            children: Vec::new(),
+            parent: None,
+            visible: true,
+        });
+        self.attach(id, parent);
+        id
+    }
+
+    pub fn node(&self, id: usize) -> Option<&Node> {
+        self.nodes.get(id)
+    }
+
+    pub fn node_mut(&mut self, id: usize) -> Option<&mut Node> {
+        self.nodes.get_mut(id)
+    }
+
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    // moves a node (and its subtree) under another parent, or to the root
+    pub fn set_parent(&mut self, id: usize, parent: Option<usize>) {
+        if id >= self.nodes.len()
+            || parent.is_some_and(|p| p >= self.nodes.len() || self.is_ancestor(id, p))
+        {
+            return;
+        }
+        self.detach(id);
+        self.attach(id, parent);
+    }
+
+    // unlinks a node and its subtree from the graph, the id is not reused
+    pub fn remove_node(&mut self, id: usize) {
+        if id < self.nodes.len() {
+            self.detach(id);
+        }
+    }
+
+    // true when `ancestor` is `id` or one of its parents
+    fn is_ancestor(&self, ancestor: usize, id: usize) -> bool {
+        let mut current = Some(id);
+        while let Some(node) = current {
+            if node == ancestor {
+                return true;
+            }
+            current = self.nodes[node].parent;
+        }
+        false
+    }
+
+    fn attach(&mut self, id: usize, parent: Option<usize>) {
+        match parent.filter(|&p| p < self.nodes.len()) {
+            Some(p) => self.nodes[p].children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes[id].parent = parent.filter(|&p| p < self.nodes.len());
+    }
+
+    fn detach(&mut self, id: usize) {
+        match self.nodes[id].parent.take() {
+            Some(p) => self.nodes[p].children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id),
+        }
+    }
+
+    // mesh space to world space of a node, its transform then its parents ones
+    pub fn world_matrix(&self, id: usize) -> Mat3D {
+        let mut matrix = Mat3D::identity();
+        let mut current = self.nodes.get(id).map(|_| id);
+        while let Some(node) = current {
+            matrix = matrix * self.nodes[node].transform.matrix();
+            current = self.nodes[node].parent;
+        }
+        matrix
+    }
+
+    // World space bounds of a node mesh and of its visible children, None when none
+    // of them holds vertices
+    pub fn node_bounds(&self, id: usize) -> Option<Bounds> {
+        let mut corners = Vec::new();
+        self.collect_corners(id, &self.world_matrix(id), &mut corners);
+        let aabb = Aabb::from_points(corners.iter())?;
+        Bounds::from_points(aabb.corners().iter())
+    }
+
+    fn collect_corners(&self, id: usize, world: &Mat3D, corners: &mut Vec<Vec3D>) {
+        let node = &self.nodes[id];
+        if let Some(bounds) = node.mesh.and_then(|m| self.mesh_bounds(m)) {
+            corners.extend(bounds.aabb.transformed(world).corners());
+        }
+        for &child in &node.children {
+            if self.nodes[child].visible {
+                let world = self.nodes[child].transform.matrix() * *world;
+                self.collect_corners(child, &world, corners);
+            }
+        }
+    }
+
+    // Calls `f` for every visible node holding a mesh, with the transforms from that node
+    // up to its root: applying them in order takes mesh space to world space.
+    // The material is the node one, else the mesh one, else the closest parent one.
+    // The group materials go with the mesh groups, None for the groups using that
+    // material; a node material overrides them all.
+    // The bounds are the mesh ones, in mesh space.
+    pub fn for_each_mesh<F>(&self, mut f: F)
+    where
+        F: FnMut(&Mesh, &[Transform], &Material, &[Option<Material>], Option<&Bounds>),
+    {
+        let mut chain = Vec::new();
+        for &root in &self.roots {
+            self.visit(root, &mut chain, None, &mut f);
+        }
+    }
+
+    fn visit<F>(&self, id: usize, chain: &mut Vec<Transform>, inherited: Option<usize>, f: &mut F)
+    where
+        F: FnMut(&Mesh, &[Transform], &Material, &[Option<Material>], Option<&Bounds>),
+    {
+        let node = &self.nodes[id];
+        if !node.visible {
+            return;
+        }
+        chain.insert(0, node.transform);
+        if let Some(m) = node.mesh.filter(|&m| m < self.meshes.len()) {
+            let material = node
+                .material
+                .or(self.mesh_materials[m])
+                .or(inherited)
+                .and_then(|id| self.materials.get(id))
+                .copied()
+                .unwrap_or_default();
+            let mesh = &self.meshes[m];
+            let groups: Vec<Option<Material>> = match node.material {
+                Some(_) => vec![None; mesh.groups.len()],
+                None => mesh
+                    .groups
+                    .iter()
+                    .map(|group| {
+                        group
+                            .material_id
+                            .and_then(|id| self.materials.get(id))
+                            .copied()
+                    })
+                    .collect(),
+            };
+            f(mesh, chain, &material, &groups, self.mesh_bounds(m));
+        }
+        for &child in &node.children {
+            self.visit(child, chain, node.material.or(inherited), f);
+        }
+        chain.remove(0);
+    }
+}
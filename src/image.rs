@@ -0,0 +1,106 @@
+use std::{error, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageError {
+    // the pixel buffer does not hold width * height RGBA pixels
+    Size { expected: usize, found: usize },
+    // the png encoder failed
+    Png(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Size { expected, found } => write!(
+                f,
+                "image: expected {} bytes of RGBA pixels, got {}",
+                expected, found
+            ),
+            ImageError::Png(message) => write!(f, "png: {}", message),
+        }
+    }
+}
+
+impl error::Error for ImageError {}
+
+fn check_size(width: u32, height: u32, rgba: &[u8]) -> Result<(), ImageError> {
+    let expected = width as usize * height as usize * 4;
+    if rgba.len() != expected {
+        return Err(ImageError::Size {
+            expected,
+            found: rgba.len(),
+        });
+    }
+    Ok(())
+}
+
+// 8 bit RGBA png, rows from top to bottom like the framebuffer
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, ImageError> {
+    check_size(width, height, rgba)?;
+    let png_error = |e: png::EncodingError| ImageError::Png(e.to_string());
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    writer.write_image_data(rgba).map_err(png_error)?;
+    writer.finish().map_err(png_error)?;
+    Ok(bytes)
+}
+
+// binary (P6) ppm, the format has no alpha so it is dropped: the cleared background
+// comes out black
+pub fn encode_ppm(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, ImageError> {
+    check_size(width, height, rgba)?;
+    let mut bytes = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    bytes.reserve(rgba.len() / 4 * 3);
+    for pixel in rgba.chunks(4) {
+        bytes.extend_from_slice(&pixel[..3]);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::Texture;
+
+    // 3x2, every channel different
+    fn pixels() -> Vec<u8> {
+        (0..24).map(|i| i * 10).collect()
+    }
+
+    #[test]
+    fn ppm() {
+        let bytes = encode_ppm(3, 2, &pixels()).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert!(bytes.starts_with(header));
+        assert_eq!(bytes.len(), header.len() + 3 * 2 * 3);
+        // alpha dropped
+        assert_eq!(
+            &bytes[header.len()..header.len() + 6],
+            &[0, 10, 20, 40, 50, 60]
+        );
+
+        let texture = Texture::decode(&bytes).unwrap();
+        assert_eq!((texture.width(), texture.height()), (3, 2));
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let bytes = encode_png(3, 2, &pixels()).unwrap();
+        let texture = Texture::from_png(&bytes).unwrap();
+        assert_eq!((texture.width(), texture.height()), (3, 2));
+        assert_eq!(texture.pixels(), &pixels()[..]);
+    }
+
+    #[test]
+    fn size() {
+        let size = Err(ImageError::Size {
+            expected: 24,
+            found: 23,
+        });
+        assert_eq!(encode_png(3, 2, &pixels()[..23]), size);
+        assert_eq!(encode_ppm(3, 2, &pixels()[..23]), size);
+    }
+}
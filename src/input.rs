@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+
+use crate::camera::{Camera, CameraMode};
+
+// a single keypress without key up moves the camera as if held this long
+const TAP_DURATION: f64 = 0.1;
+// longest frame taken into account, avoids jumps after the tab was in background
+const MAX_FRAME_TIME: f64 = 0.25;
+
+// Keyboard and mouse state, turned into camera motion once per frame.
+// Speeds are per second so motion does not depend on the frame rate.
+pub struct Input {
+    held: HashSet<String>,
+    tapped: Vec<String>,
+    dragging: bool,
+    // mouse motion and wheel accumulated since last frame
+    mouse_dx: f64,
+    mouse_dy: f64,
+    wheel: f64,
+    last_time: Option<f64>,
+    pub move_speed: f64, // world units per second
+    pub turn_speed: f64, // radians per second, arrow keys
+    pub look_speed: f64, // radians per pixel of mouse drag
+    pub zoom_speed: f64, // per wheel unit: fraction of orbit distance, or seconds of motion
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// "W" and "w" are the same key
+fn normalize(key: &str) -> String {
+    if key.chars().count() == 1 {
+        key.to_lowercase()
+    } else {
+        key.to_string()
+    }
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input {
+            held: HashSet::new(),
+            tapped: Vec::new(),
+            dragging: false,
+            mouse_dx: 0.0,
+            mouse_dy: 0.0,
+            wheel: 0.0,
+            last_time: None,
+            move_speed: 2.0,
+            turn_speed: 1.5,
+            look_speed: 0.005,
+            zoom_speed: 0.001,
+        }
+    }
+
+    pub fn key_down(&mut self, key: &str) {
+        self.held.insert(normalize(key));
+    }
+
+    pub fn key_up(&mut self, key: &str) {
+        self.held.remove(&normalize(key));
+    }
+
+    // key event without a matching key up, like the keypress DOM event
+    pub fn key_press(&mut self, key: &str) {
+        self.tapped.push(normalize(key));
+    }
+
+    pub fn mouse_down(&mut self) {
+        self.dragging = true;
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.dragging = false;
+    }
+
+    // pixels moved since the last event, only used while the button is down
+    pub fn mouse_move(&mut self, dx: f64, dy: f64) {
+        if self.dragging {
+            self.mouse_dx += dx;
+            self.mouse_dy += dy;
+        }
+    }
+
+    // positive moves away, like the DOM wheel deltaY
+    pub fn wheel(&mut self, delta: f64) {
+        self.wheel += delta;
+    }
+
+    // seconds of motion along an axis this frame: the whole frame while a key is held,
+    // plus TAP_DURATION per keypress
+    fn axis(&self, positive: &[&str], negative: &[&str], dt: f64) -> f64 {
+        let amount = |keys: &[&str]| -> f64 {
+            let held = keys.iter().any(|k| self.held.contains(*k)) as u8 as f64;
+            let taps = self
+                .tapped
+                .iter()
+                .filter(|t| keys.contains(&t.as_str()))
+                .count() as f64;
+            held * dt + taps * TAP_DURATION
+        };
+        amount(positive) - amount(negative)
+    }
+
+    // Moves the camera for a frame ending at `time` seconds.
+    // WASD moves (orbits in orbit mode), Q / E go down / up, arrows and mouse drag look
+    // around, the wheel zooms.
+    pub fn update(&mut self, camera: &mut Camera, time: f64) {
+        let dt = match self.last_time {
+            Some(last) => (time - last).clamp(0.0, MAX_FRAME_TIME),
+            None => 0.0,
+        };
+        self.last_time = Some(time);
+
+        let forward = self.axis(&["w"], &["s"], dt);
+        let strafe = self.axis(&["d"], &["a"], dt);
+        let rise = self.axis(&["e"], &["q"], dt);
+        let turn = self.axis(&["ArrowRight"], &["ArrowLeft"], dt);
+        let tilt = self.axis(&["ArrowUp"], &["ArrowDown"], dt);
+
+        camera.rotate(
+            -turn * self.turn_speed - self.mouse_dx * self.look_speed,
+            tilt * self.turn_speed - self.mouse_dy * self.look_speed,
+            0.0,
+        );
+
+        if camera.mode == CameraMode::Orbit {
+            camera.rotate(-strafe * self.turn_speed, forward * self.turn_speed, 0.0);
+            camera.distance *= (1.0 + self.wheel * self.zoom_speed).max(0.1);
+        } else {
+            let (look, right, up) = camera.axes();
+            let zoom = -self.wheel * self.zoom_speed;
+            camera.pos = camera.pos
+                + look.scale((forward + zoom) * self.move_speed)
+                + right.scale(strafe * self.move_speed)
+                + up.scale(rise * self.move_speed);
+        }
+
+        self.tapped.clear();
+        self.mouse_dx = 0.0;
+        self.mouse_dy = 0.0;
+        self.wheel = 0.0;
+    }
+
+    pub fn is_held(&self, key: &str) -> bool {
+        self.held.contains(&normalize(key))
+    }
+}
@@ -1,13 +1,36 @@
+#![allow(non_snake_case)] // crate and module names predate the snake case convention
+
 extern crate cfg_if;
 extern crate wasm_bindgen;
 extern crate web_sys;
 
 // graphical pipeline
-mod scene;
+pub mod scene;
+
+pub mod bounds; // boxes and spheres around meshes, frustum tests
+pub mod clip; // triangles cut to the view frustum in clip space
+pub mod framebuffer; // software RGBA render buffer
+pub mod image; // png / ppm encoders
+pub mod rasterizer; // triangles to pixels, depth tested
+pub mod target; // where frames are presented: canvas or headless
+pub mod texture; // png / ppm images sampled by uv
+
+pub mod transforms; // matrix for rotation and translation
+pub mod tri3D;
+pub mod vec2D;
+pub mod vec3D;
+pub mod vertex; // per corner attributes: normal, uv
 
-mod transforms; // matrix for rotation and translation
-mod tri3D;
-mod vec3D;
+pub mod animation; // keyframed node transforms
+pub mod camera;
+pub mod graph; // nodes placing meshes in the world
+pub mod input; // keyboard and mouse to camera motion
+pub mod light; // directional, point and spot lights
+pub mod loader; // stl object loader
+pub mod material; // surface colors
+pub mod mesh;
+pub mod obj; // wavefront obj and mtl loader
+pub mod projection; // perspective or orthographic lens
 
-mod camera;
-mod loader; // stl object loader
+pub use framebuffer::Framebuffer;
+pub use scene::Scene;
@@ -0,0 +1,201 @@
+use wasm_bindgen::prelude::*;
+
+use crate::material::Material;
+use crate::vec3D::Vec3D;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    // sun like, same direction everywhere, no falloff
+    Directional = 0,
+    // shines in every direction from `position`
+    Point = 1,
+    // point light restricted to a cone around `direction`
+    Spot = 2,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadingMode {
+    // one color per triangle, from its face normal
+    Flat = 0,
+    // lighting computed at the corners and interpolated across the triangle
+    Gouraud = 1,
+    // normal interpolated across the triangle, lighting computed for every pixel
+    Phong = 2,
+}
+
+// Colors are linear RGB, usually in [0, 1], scaled by `intensity`.
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f64; 3],
+    pub intensity: f64,
+    // point and spot only
+    pub position: Vec3D,
+    // where the light travels to, directional and spot only
+    pub direction: Vec3D,
+    // constant, linear and quadratic terms of 1 / (c + l d + q d²), point and spot only
+    pub attenuation: [f64; 3],
+    // half angles of the spot cone in radians: full light inside `inner`, none past `outer`
+    pub inner_angle: f64,
+    pub outer_angle: f64,
+    pub enabled: bool,
+}
+
+impl Light {
+    pub fn new(kind: LightKind) -> Light {
+        Light {
+            kind,
+            color: [1.0; 3],
+            intensity: 1.0,
+            position: Vec3D::new(0.0, 0.0, 0.0),
+            direction: Vec3D::new(0.0, 0.0, 1.0),
+            attenuation: [1.0, 0.0, 0.0],
+            inner_angle: 0.3,
+            outer_angle: 0.5,
+            enabled: true,
+        }
+    }
+
+    pub fn directional(direction: Vec3D) -> Light {
+        Light {
+            direction,
+            ..Light::new(LightKind::Directional)
+        }
+    }
+
+    pub fn point(position: Vec3D) -> Light {
+        Light {
+            position,
+            ..Light::new(LightKind::Point)
+        }
+    }
+
+    pub fn spot(position: Vec3D, direction: Vec3D) -> Light {
+        Light {
+            position,
+            direction,
+            ..Light::new(LightKind::Spot)
+        }
+    }
+
+    // unit vector from `point` towards the light and the light strength reaching it
+    pub fn incoming(&self, point: &Vec3D) -> (Vec3D, f64) {
+        if self.kind == LightKind::Directional {
+            return (self.direction.scale(-1.0).normalized(), self.intensity);
+        }
+        let to_light = self.position - *point;
+        let distance = to_light.length();
+        if distance < 1e-9 {
+            return (Vec3D::new(0.0, 0.0, 0.0), 0.0);
+        }
+        let to_light = to_light.scale(1.0 / distance);
+        let [c, l, q] = self.attenuation;
+        let mut strength = self.intensity / (c + l * distance + q * distance * distance).max(1e-9);
+
+        if self.kind == LightKind::Spot {
+            // smooth edge between the inner and outer cones
+            let cos = -to_light.dot_product(self.direction.normalized());
+            let (cos_inner, cos_outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+            let t = if cos_inner > cos_outer {
+                ((cos - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0)
+            } else {
+                (cos >= cos_inner) as u8 as f64
+            };
+            strength *= t * t * (3.0 - 2.0 * t);
+        }
+        (to_light, strength)
+    }
+}
+
+// Every light of a scene plus the ambient term lighting surfaces from all sides.
+// Ids returned by `add` stay valid, removed lights leave an empty slot.
+#[derive(Debug, Clone)]
+pub struct Lighting {
+    pub ambient: [f64; 3],
+    lights: Vec<Option<Light>>,
+}
+
+impl Default for Lighting {
+    // dim ambient and a white light coming from above, behind the default camera
+    fn default() -> Self {
+        let mut lighting = Lighting::new();
+        lighting.ambient = [0.1; 3];
+        lighting.add(Light::directional(Vec3D::new(0.0, -1.0, 1.0)));
+        lighting
+    }
+}
+
+impl Lighting {
+    // no light at all, everything is black
+    pub fn new() -> Lighting {
+        Lighting {
+            ambient: [0.0; 3],
+            lights: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, light: Light) -> usize {
+        self.lights.push(Some(light));
+        self.lights.len() - 1
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Light> {
+        self.lights.get(id).and_then(|light| light.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Light> {
+        self.lights.get_mut(id).and_then(|light| light.as_mut())
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        if let Some(light) = self.lights.get_mut(id) {
+            *light = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear();
+    }
+
+    pub fn lights(&self) -> impl Iterator<Item = &Light> {
+        self.lights.iter().flatten().filter(|light| light.enabled)
+    }
+
+    // Color of a surface at `point` facing `normal` (unit length) seen from `eye`:
+    // emissive, plus diffuse times ambient and Lambert terms, plus Phong highlights
+    pub fn shade(
+        &self,
+        point: &Vec3D,
+        normal: &Vec3D,
+        eye: &Vec3D,
+        material: &Material,
+    ) -> [f64; 3] {
+        let to_eye = (*eye - *point).normalized();
+        let mut diffuse = self.ambient;
+        let mut specular = [0.0; 3];
+        for light in self.lights() {
+            let (to_light, strength) = light.incoming(point);
+            let lambert = normal.dot_product(to_light);
+            if lambert <= 0.0 || strength == 0.0 {
+                continue;
+            }
+            // to_light mirrored around the normal
+            let reflected = normal.scale(2.0 * lambert) - to_light;
+            let highlight = match reflected.dot_product(to_eye) {
+                cos if cos > 0.0 => cos.powf(material.shininess),
+                _ => 0.0,
+            };
+            for i in 0..3 {
+                diffuse[i] += light.color[i] * strength * lambert;
+                specular[i] += light.color[i] * strength * highlight;
+            }
+        }
+        let mut res = material.emissive;
+        for i in 0..3 {
+            res[i] += material.diffuse[i] * diffuse[i] + material.specular[i] * specular[i];
+        }
+        res
+    }
+}
@@ -1,64 +1,345 @@
-use std::io::{BufReader, Read};
+use std::{error, fmt, str};
 
-use crate::{tri3D::Tri3D, vec3D::Vec3D};
+use crate::{
+    mesh::{Mesh, CREASE_ANGLE},
+    tri3D::Tri3D,
+    vec3D::Vec3D,
+};
 
-fn read_float(buf_reader: &mut BufReader<&[u8]>) -> f64 {
-    let mut float_buffer = [0u8; std::mem::size_of::<f32>()];
-    let e = buf_reader.read_exact(&mut float_buffer);
-    if e.is_err() {
-        panic!("{:?}", e);
+const HEADER_LEN: usize = 80;
+// normal + 3 vertices: 12 * f32, attribute byte count: u16
+const FACET_LEN: usize = 12 * 4 + 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StlError {
+    // not even room for the 80 bytes header and the triangle count
+    TooShort(usize),
+    // binary: the header triangle count makes the file `expected` bytes long, it has `found`
+    Length {
+        triangles: u32,
+        expected: u64,
+        found: usize,
+    },
+    // ascii: unexpected token or number at `line`
+    Syntax {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for StlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StlError::TooShort(len) => write!(f, "stl: {} bytes is too short for a header", len),
+            StlError::Length {
+                triangles,
+                expected,
+                found,
+            } => write!(
+                f,
+                "stl: header announces {} triangles, expected {} bytes but found {}",
+                triangles, expected, found
+            ),
+            StlError::Syntax { line, message } => write!(f, "stl: line {}: {}", line, message),
+        }
     }
-    f32::from_le_bytes(float_buffer) as f64
 }
 
-pub fn load_teapot() -> Vec<Tri3D> {
-    let teapot = include_bytes!("teapot.stl");
-    let mut res = Vec::new();
+impl error::Error for StlError {}
 
-    // header 80 bytes + nb_triangles u32: 4 bytes, little endian
-    let mut buf_reader = BufReader::new(&teapot[..]);
-    let mut header: [u8; 80] = [0; 80];
-    if buf_reader.read_exact(&mut header).is_err() {
-        return Vec::new();
+// Parses ASCII or binary STL, whichever `bytes` holds.
+// Binary files are allowed to start with "solid" too, so a buffer whose length matches its
+// binary triangle count exactly is always read as binary.
+pub fn load_stl(bytes: &[u8]) -> Result<Mesh, StlError> {
+    if is_binary_stl(bytes) {
+        return load_binary_stl(bytes);
     }
-    let mut u32_buf = [0u8; std::mem::size_of::<u32>()];
-    if buf_reader.read_exact(&mut u32_buf).is_err() {
-        return Vec::new();
+    match str::from_utf8(bytes) {
+        Ok(text) if text.trim_start().starts_with("solid") => load_ascii_stl(text),
+        _ => load_binary_stl(bytes),
     }
+}
 
-    let nb_tris = u32::from_le_bytes(u32_buf);
-
-    for _ in 0..nb_tris {
-        // normal vector: 3 * f32 -- ignored
-        // vertex 1: 3 * f32
-        // vertex 2: 3 * f32
-        // vertex 3: 3 * f32
-        // attribute byte count: 2 bytes, everything little endian -- ignored
-        let _normal_x = read_float(&mut buf_reader);
-        let _normal_y = read_float(&mut buf_reader);
-        let _normal_z = read_float(&mut buf_reader);
-        res.push(Tri3D::new(
-            Vec3D::new(
-                read_float(&mut buf_reader),
-                read_float(&mut buf_reader),
-                read_float(&mut buf_reader),
-            ),
-            Vec3D::new(
-                read_float(&mut buf_reader),
-                read_float(&mut buf_reader),
-                read_float(&mut buf_reader),
-            ),
-            Vec3D::new(
-                read_float(&mut buf_reader),
-                read_float(&mut buf_reader),
-                read_float(&mut buf_reader),
-            ),
+fn binary_triangle_count(bytes: &[u8]) -> Option<u32> {
+    let count = bytes.get(HEADER_LEN..HEADER_LEN + 4)?;
+    Some(u32::from_le_bytes([count[0], count[1], count[2], count[3]]))
+}
+
+// in u64, a usize may not hold it on 32 bits targets
+fn binary_len(nb_tris: u32) -> u64 {
+    (HEADER_LEN + 4) as u64 + nb_tris as u64 * FACET_LEN as u64
+}
+
+fn is_binary_stl(bytes: &[u8]) -> bool {
+    match binary_triangle_count(bytes) {
+        Some(n) => binary_len(n) == bytes.len() as u64,
+        None => false,
+    }
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f64 {
+    let b = &bytes[offset..offset + 4];
+    f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64
+}
+
+fn read_vec(bytes: &[u8], offset: usize) -> Vec3D {
+    Vec3D::new(
+        read_f32(bytes, offset),
+        read_f32(bytes, offset + 4),
+        read_f32(bytes, offset + 8),
+    )
+}
+
+// header 80 bytes + nb_triangles u32: 4 bytes, little endian, then per triangle:
+// normal vector: 3 * f32
+// vertex 1: 3 * f32
+// vertex 2: 3 * f32
+// vertex 3: 3 * f32
+// attribute byte count: 2 bytes, everything little endian
+fn load_binary_stl(bytes: &[u8]) -> Result<Mesh, StlError> {
+    let nb_tris = match binary_triangle_count(bytes) {
+        Some(n) => n,
+        None => return Err(StlError::TooShort(bytes.len())),
+    };
+    if binary_len(nb_tris) != bytes.len() as u64 {
+        return Err(StlError::Length {
+            triangles: nb_tris,
+            expected: binary_len(nb_tris),
+            found: bytes.len(),
+        });
+    }
+    let data = &bytes[HEADER_LEN + 4..];
+
+    let mut tris = Vec::with_capacity(nb_tris as usize);
+    let mut normals = Vec::with_capacity(nb_tris as usize);
+    let mut attributes = Vec::with_capacity(nb_tris as usize);
+    for facet in data.chunks_exact(FACET_LEN) {
+        tris.push(Tri3D::new(
+            read_vec(facet, 12),
+            read_vec(facet, 24),
+            read_vec(facet, 36),
         ));
-        let mut attribute_byte_count = [0u8; std::mem::size_of::<u16>()];
-        let e = buf_reader.read_exact(&mut attribute_byte_count);
-        if e.is_err() {
-            panic!("{:?}", e);
+        normals.push(read_vec(facet, 0));
+        attributes.push(u16::from_le_bytes([facet[48], facet[49]]));
+    }
+
+    let mut mesh = Mesh::from_facets(&tris, CREASE_ANGLE);
+    mesh.normals = normals;
+    mesh.attributes = attributes;
+    Ok(mesh)
+}
+
+// solid name
+//   facet normal nx ny nz
+//     outer loop
+//       vertex x y z (3 times)
+//     endloop
+//   endfacet
+// endsolid name
+fn load_ascii_stl(text: &str) -> Result<Mesh, StlError> {
+    let mut tris = Vec::new();
+    let mut normals = Vec::new();
+    let mut normal = Vec3D::new(0.0, 0.0, 0.0);
+    let mut vertices = Vec::with_capacity(3);
+
+    for (i, line) in text.lines().enumerate() {
+        let line_nb = i + 1;
+        let syntax = |message: String| StlError::Syntax {
+            line: line_nb,
+            message,
+        };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let read_xyz = |tokens: &mut str::SplitWhitespace| -> Result<Vec3D, StlError> {
+            let mut xyz = [0.0; 3];
+            for coord in xyz.iter_mut() {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| syntax("missing coordinate".to_string()))?;
+                *coord = token
+                    .parse()
+                    .map_err(|_| syntax(format!("invalid number {:?}", token)))?;
+            }
+            Ok(Vec3D::new(xyz[0], xyz[1], xyz[2]))
+        };
+
+        match keyword {
+            "solid" | "endsolid" | "outer" | "endloop" => {}
+            "facet" => {
+                if tokens.next() != Some("normal") {
+                    return Err(syntax("expected \"facet normal\"".to_string()));
+                }
+                normal = read_xyz(&mut tokens)?;
+                vertices.clear();
+            }
+            "vertex" => {
+                if vertices.len() == 3 {
+                    return Err(syntax("more than 3 vertices in facet".to_string()));
+                }
+                vertices.push(read_xyz(&mut tokens)?);
+            }
+            "endfacet" => {
+                if vertices.len() != 3 {
+                    return Err(syntax(format!(
+                        "facet has {} vertices instead of 3",
+                        vertices.len()
+                    )));
+                }
+                tris.push(Tri3D::new(vertices[0], vertices[1], vertices[2]));
+                normals.push(normal);
+                vertices.clear();
+            }
+            other => return Err(syntax(format!("unexpected keyword {:?}", other))),
         }
     }
-    res
+
+    let mut mesh = Mesh::from_facets(&tris, CREASE_ANGLE);
+    mesh.normals = normals;
+    Ok(mesh)
+}
+
+pub fn load_teapot() -> Mesh {
+    load_stl(include_bytes!("teapot.stl")).expect("embedded teapot is a valid stl")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two triangles sharing an edge, facet normals +z
+    const SQUARE: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    fn binary_stl(header: &[u8], tris: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(HEADER_LEN, b' ');
+        bytes.extend_from_slice(&(tris.len() as u32).to_le_bytes());
+        for (i, tri) in tris.iter().enumerate() {
+            for value in [0.0f32, 0.0, 1.0].iter().chain(tri.iter().flatten()) {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&(i as u16).to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn ascii() {
+        let text = "solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid square
+";
+        let mesh = load_stl(text.as_bytes()).unwrap();
+        assert_eq!(mesh.indices.len(), 2);
+        // the shared edge is welded
+        assert_eq!(mesh.vertices.len(), 4);
+        let normal = mesh.normals[1];
+        assert_eq!((normal.x, normal.y, normal.z), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn hard_edges() {
+        // unit cube, two triangles per side wound counter clockwise seen from outside
+        let p = |i: usize| [(i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32];
+        let quads = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ];
+        let tris: Vec<[[f32; 3]; 3]> = quads
+            .iter()
+            .flat_map(|q| vec![[p(q[0]), p(q[1]), p(q[2])], [p(q[0]), p(q[2]), p(q[3])]])
+            .collect();
+        let mesh = load_stl(&binary_stl(b"cube", &tris)).unwrap();
+
+        // one vertex per side at each corner, and the one at the origin faces -x, -y, -z
+        assert_eq!(mesh.vertices.len(), 24);
+        let mut normals: Vec<(f64, f64, f64)> = mesh
+            .vertices
+            .iter()
+            .filter(|v| v.pos.length() == 0.0)
+            .map(|v| (v.attr.normal.x, v.attr.normal.y, v.attr.normal.z))
+            .collect();
+        normals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            normals,
+            vec![(-1.0, 0.0, 0.0), (0.0, -1.0, 0.0), (0.0, 0.0, -1.0)]
+        );
+    }
+
+    #[test]
+    fn ascii_syntax_error() {
+        let text = "solid broken\n  facet normal 0 0 1\n    vertex 0 0 zero\n";
+        assert_eq!(
+            load_stl(text.as_bytes()).unwrap_err(),
+            StlError::Syntax {
+                line: 3,
+                message: "invalid number \"zero\"".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn binary() {
+        let mesh = load_stl(&binary_stl(b"square", &SQUARE)).unwrap();
+        assert_eq!(mesh.indices.len(), 2);
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.attributes, vec![0, 1]);
+        let pos = mesh.vertices[2].pos;
+        assert_eq!((pos.x, pos.y, pos.z), (1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn binary_starting_with_solid() {
+        let mesh = load_stl(&binary_stl(b"solid square", &SQUARE)).unwrap();
+        assert_eq!(mesh.indices.len(), 2);
+    }
+
+    #[test]
+    fn truncated_binary() {
+        let mut bytes = binary_stl(b"square", &SQUARE);
+        bytes.truncate(bytes.len() - 10);
+        assert_eq!(
+            load_stl(&bytes).unwrap_err(),
+            StlError::Length {
+                triangles: 2,
+                expected: 184,
+                found: 174,
+            }
+        );
+        assert_eq!(load_stl(&bytes[..40]).unwrap_err(), StlError::TooShort(40));
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        assert_eq!(
+            load_stl(&[0; 90]).unwrap_err(),
+            StlError::Length {
+                triangles: 0,
+                expected: 84,
+                found: 90,
+            }
+        );
+    }
 }
@@ -0,0 +1,55 @@
+use crate::obj::MtlMaterial;
+
+// How a surface reacts to light, colors are linear RGB in [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    // base color, reflected in every direction
+    pub diffuse: [f64; 3],
+    // color and sharpness of the highlights, black for a matte surface
+    pub specular: [f64; 3],
+    pub shininess: f64,
+    // light given off by the surface itself, visible without any light
+    pub emissive: [f64; 3],
+    // 1 is opaque, 0 invisible
+    pub opacity: f64,
+    // index in SceneGraph textures, multiplies diffuse and opacity
+    pub texture: Option<usize>,
+}
+
+impl Default for Material {
+    // white plastic
+    fn default() -> Self {
+        Material {
+            diffuse: [1.0; 3],
+            specular: [0.5; 3],
+            shininess: 32.0,
+            emissive: [0.0; 3],
+            opacity: 1.0,
+            texture: None,
+        }
+    }
+}
+
+impl Material {
+    pub fn new(diffuse: [f64; 3]) -> Material {
+        Material {
+            diffuse,
+            ..Material::default()
+        }
+    }
+}
+
+impl From<&MtlMaterial> for Material {
+    fn from(mtl: &MtlMaterial) -> Material {
+        Material {
+            diffuse: mtl.diffuse,
+            specular: mtl.specular,
+            // Ns 0 would light the whole hemisphere, it means "no highlight" in practice
+            shininess: mtl.shininess.max(1.0),
+            emissive: mtl.emissive,
+            opacity: mtl.opacity.clamp(0.0, 1.0),
+            // map_Kd is a file name, the caller loads it and sets the texture
+            texture: None,
+        }
+    }
+}
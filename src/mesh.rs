@@ -0,0 +1,195 @@
+use std::{collections::HashMap, f64::consts::FRAC_PI_4};
+
+use crate::{tri3D::Tri3D, vec3D::Vec3D, vertex::Vertex};
+
+// from_facets angle for models with flat facets only: triangles meeting at a sharper
+// angle keep separate vertex normals, so box edges stay hard while curved surfaces
+// made of small facets get smooth
+pub const CREASE_ANGLE: f64 = FRAC_PI_4;
+
+// Named run of consecutive triangles (OBJ `o`, `g` and `usemtl` statements)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Group {
+    pub object: String,
+    pub name: String,
+    // name of a material from one of the mesh material_libs
+    pub material: Option<String>,
+    // index in SceneGraph materials of that material once its library is linked, the
+    // mesh material is used until then
+    pub material_id: Option<usize>,
+    pub start: usize,
+    pub len: usize,
+}
+
+// Indexed triangle mesh: corners shared by several triangles are stored once in `vertices`,
+// so the pipeline transforms each of them once per frame.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    // three indices into vertices per triangle
+    pub indices: Vec<[u32; 3]>,
+    // facet normal as written in the file, one per triangle, may be zero
+    pub normals: Vec<Vec3D>,
+    // STL "attribute byte count" word, one per triangle, 0 when the format has none
+    pub attributes: Vec<u16>,
+    // empty when the format has no notion of groups
+    pub groups: Vec<Group>,
+    // material files referenced by the model (OBJ `mtllib`)
+    pub material_libs: Vec<String>,
+}
+
+fn position_key(p: &Vec3D) -> [u64; 3] {
+    [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+}
+
+// unit normal of a triangle from its winding, None when it is degenerate
+fn face_normal(p: &[Vec3D; 3]) -> Option<Vec3D> {
+    let normal = (p[1] - p[0]).cross_product(p[2] - p[0]);
+    if normal.length() == 0.0 {
+        return None;
+    }
+    Some(normal.normalized())
+}
+
+// angle of a triangle at one of its corners, NaN when an edge has no length
+fn corner_angle(p: &[Vec3D; 3], corner: usize) -> f64 {
+    let e1 = p[(corner + 1) % 3] - p[corner];
+    let e2 = p[(corner + 2) % 3] - p[corner];
+    (e1.dot_product(e2) / (e1.length() * e2.length()))
+        .clamp(-1.0, 1.0)
+        .acos()
+}
+
+// exact bit pattern of a vertex, identical corners get welded together
+fn vertex_key(vertex: &Vertex) -> [u64; 8] {
+    let (p, n, uv) = (vertex.pos, vertex.attr.normal, vertex.attr.uv);
+    [
+        p.x.to_bits(),
+        p.y.to_bits(),
+        p.z.to_bits(),
+        n.x.to_bits(),
+        n.y.to_bits(),
+        n.z.to_bits(),
+        uv.x.to_bits(),
+        uv.y.to_bits(),
+    ]
+}
+
+impl Mesh {
+    pub fn new() -> Mesh {
+        Mesh::default()
+    }
+
+    // Welds identical corners of a flat triangle list and generates the missing vertex
+    // normals. Per triangle data (normals, attributes) is zero, callers that have some
+    // overwrite it afterwards.
+    pub fn from_tris(tris: &[Tri3D]) -> Mesh {
+        let mut mesh = Mesh::new();
+        let mut seen: HashMap<[u64; 8], u32> = HashMap::new();
+
+        for tri in tris {
+            let mut face = [0u32; 3];
+            for (i, index) in face.iter_mut().enumerate() {
+                let vertex = Vertex::new(tri[i as u8], tri.attrs[i]);
+                *index = *seen.entry(vertex_key(&vertex)).or_insert_with(|| {
+                    mesh.vertices.push(vertex);
+                    (mesh.vertices.len() - 1) as u32
+                });
+            }
+            mesh.indices.push(face);
+        }
+        mesh.normals = vec![Vec3D::new(0.0, 0.0, 0.0); tris.len()];
+        mesh.attributes = vec![0; tris.len()];
+        mesh.generate_normals();
+        mesh
+    }
+
+    // from_tris for formats without vertex normals (STL). Each corner gets the angle
+    // weighted average of the normals of the triangles around it that bend by less than
+    // `crease` radians from its own, so corners only weld across smooth edges and the
+    // sharper ones stay hard.
+    pub fn from_facets(tris: &[Tri3D], crease: f64) -> Mesh {
+        let corners: Vec<[Vec3D; 3]> = tris.iter().map(|tri| [tri.a, tri.b, tri.c]).collect();
+        let faces: Vec<Option<Vec3D>> = corners.iter().map(face_normal).collect();
+        // (triangle, corner) of every corner at a position
+        let mut around: HashMap<[u64; 3], Vec<(usize, usize)>> = HashMap::new();
+        for (t, p) in corners.iter().enumerate() {
+            for (corner, pos) in p.iter().enumerate() {
+                around
+                    .entry(position_key(pos))
+                    .or_default()
+                    .push((t, corner));
+            }
+        }
+
+        let min_cos = crease.cos();
+        let mut smoothed = tris.to_vec();
+        for (t, tri) in smoothed.iter_mut().enumerate() {
+            let own = match faces[t] {
+                Some(normal) => normal,
+                None => continue,
+            };
+            for (corner, attr) in tri.attrs.iter_mut().enumerate() {
+                let mut sum = Vec3D::new(0.0, 0.0, 0.0);
+                for &(other, other_corner) in &around[&position_key(&corners[t][corner])] {
+                    let angle = corner_angle(&corners[other], other_corner);
+                    match faces[other] {
+                        Some(normal) if normal.dot_product(own) >= min_cos && angle.is_finite() => {
+                            sum = sum + normal.scale(angle)
+                        }
+                        _ => {}
+                    }
+                }
+                if sum.length() > 0.0 {
+                    attr.normal = sum.normalized();
+                }
+            }
+        }
+        Mesh::from_tris(&smoothed)
+    }
+
+    // Gives every vertex without a normal the average of the normals of the triangles
+    // around it, each weighted by the angle of the triangle at that corner so the result
+    // does not depend on how the surface is split into triangles.
+    pub fn generate_normals(&mut self) {
+        let mut sums = vec![Vec3D::new(0.0, 0.0, 0.0); self.vertices.len()];
+        for face in &self.indices {
+            let p = face.map(|i| self.vertices[i as usize].pos);
+            let normal = match face_normal(&p) {
+                Some(normal) => normal,
+                None => continue,
+            };
+            for corner in 0..3 {
+                let angle = corner_angle(&p, corner);
+                if angle.is_finite() {
+                    let sum = &mut sums[face[corner] as usize];
+                    *sum = *sum + normal.scale(angle);
+                }
+            }
+        }
+        for (vertex, sum) in self.vertices.iter_mut().zip(sums) {
+            if vertex.attr.normal.length() == 0.0 && sum.length() > 0.0 {
+                vertex.attr.normal = sum.normalized();
+            }
+        }
+    }
+
+    // flat triangle list, three vertex copies per triangle
+    pub fn to_tris(&self) -> Vec<Tri3D> {
+        (0..self.len()).map(|i| self.tri(i)).collect()
+    }
+
+    pub fn tri(&self, i: usize) -> Tri3D {
+        let [a, b, c] = self.indices[i].map(|index| self.vertices[index as usize]);
+        Tri3D::new_with_attrs(a.pos, b.pos, c.pos, [a.attr, b.attr, c.attr])
+    }
+
+    // number of triangles
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
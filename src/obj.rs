@@ -0,0 +1,376 @@
+use std::{error, fmt, str::SplitWhitespace};
+
+use crate::{
+    mesh::{Group, Mesh},
+    tri3D::Tri3D,
+    vec2D::Vec2D,
+    vec3D::Vec3D,
+    vertex::VertexAttr,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjError {
+    // malformed statement at `line`
+    Syntax { line: usize, message: String },
+    // face refers to a v, vt or vn that does not exist (yet)
+    Index { line: usize, index: i64 },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Syntax { line, message } => write!(f, "obj: line {}: {}", line, message),
+            ObjError::Index { line, index } => {
+                write!(f, "obj: line {}: index {} out of range", line, index)
+            }
+        }
+    }
+}
+
+impl error::Error for ObjError {}
+
+// One `newmtl` block of a .mtl file, colors are linear RGB in [0, 1]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub ambient: [f64; 3],           // Ka
+    pub diffuse: [f64; 3],           // Kd
+    pub specular: [f64; 3],          // Ks
+    pub emissive: [f64; 3],          // Ke
+    pub shininess: f64,              // Ns
+    pub opacity: f64,                // d, or 1 - Tr
+    pub diffuse_map: Option<String>, // map_Kd
+}
+
+impl MtlMaterial {
+    pub fn new(name: &str) -> MtlMaterial {
+        MtlMaterial {
+            name: name.to_string(),
+            ambient: [0.0; 3],
+            diffuse: [0.8; 3],
+            specular: [0.0; 3],
+            emissive: [0.0; 3],
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_map: None,
+        }
+    }
+}
+
+// statements of both formats are one per line, `#` starts a comment, `\` continues a line
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut res = Vec::new();
+    let mut pending = String::new();
+    let mut start = 0;
+    for (i, line) in text.lines().enumerate() {
+        if pending.is_empty() {
+            start = i + 1;
+        }
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        match line.trim_end().strip_suffix('\\') {
+            Some(continued) => {
+                pending.push_str(continued);
+                pending.push(' ');
+            }
+            None => {
+                pending.push_str(line);
+                res.push((start, std::mem::take(&mut pending)));
+            }
+        }
+    }
+    if !pending.is_empty() {
+        res.push((start, pending));
+    }
+    res
+}
+
+fn syntax(line: usize, message: String) -> ObjError {
+    ObjError::Syntax { line, message }
+}
+
+fn read_floats(
+    line: usize,
+    tokens: SplitWhitespace,
+    min: usize,
+    out: &mut [f64],
+) -> Result<(), ObjError> {
+    let mut count = 0;
+    for token in tokens.take(out.len()) {
+        out[count] = token
+            .parse()
+            .map_err(|_| syntax(line, format!("invalid number {:?}", token)))?;
+        count += 1;
+    }
+    if count < min {
+        return Err(syntax(
+            line,
+            format!("expected {} numbers, got {}", min, count),
+        ));
+    }
+    Ok(())
+}
+
+// 1 based, negative counts back from the last element defined so far
+fn resolve(line: usize, token: &str, len: usize) -> Result<usize, ObjError> {
+    let index: i64 = token
+        .parse()
+        .map_err(|_| syntax(line, format!("invalid index {:?}", token)))?;
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(ObjError::Index { line, index });
+    }
+    Ok(resolved as usize)
+}
+
+// Parses a Wavefront .obj file: v, vt, vn and f (any polygon, fan triangulated),
+// o / g / usemtl become mesh groups and mtllib names end up in mesh.material_libs,
+// the groups get their materials once Scene::link_materials is given those libraries.
+// Corners without vt or vn get a zero uv or normal.
+pub fn load_obj(bytes: &[u8]) -> Result<Mesh, ObjError> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut tris = Vec::new();
+    let mut groups = Vec::new();
+    let mut material_libs = Vec::new();
+
+    let mut positions: Vec<Vec3D> = Vec::new();
+    let mut uvs: Vec<Vec2D> = Vec::new();
+    let mut normals: Vec<Vec3D> = Vec::new();
+    let mut group = Group::default();
+
+    for (line, statement) in logical_lines(&text) {
+        let mut tokens = statement.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let rest = || {
+            statement
+                .split_whitespace()
+                .skip(1)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        match keyword {
+            "v" => {
+                let mut xyz = [0.0; 3];
+                read_floats(line, tokens, 3, &mut xyz)?;
+                positions.push(Vec3D::new(xyz[0], xyz[1], xyz[2]));
+            }
+            "vt" => {
+                let mut uv = [0.0; 2];
+                read_floats(line, tokens, 1, &mut uv)?;
+                uvs.push(Vec2D::new(uv[0], uv[1]));
+            }
+            "vn" => {
+                let mut xyz = [0.0; 3];
+                read_floats(line, tokens, 3, &mut xyz)?;
+                normals.push(Vec3D::new(xyz[0], xyz[1], xyz[2]));
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                for corner in tokens {
+                    let mut refs = corner.split('/');
+                    let v = resolve(line, refs.next().unwrap_or(""), positions.len())?;
+                    let uv = match refs.next() {
+                        Some(vt) if !vt.is_empty() => uvs[resolve(line, vt, uvs.len())?],
+                        _ => Vec2D::default(),
+                    };
+                    let normal = match refs.next() {
+                        Some(vn) if !vn.is_empty() => normals[resolve(line, vn, normals.len())?],
+                        _ => Vec3D::new(0.0, 0.0, 0.0),
+                    };
+                    corners.push((positions[v], VertexAttr::new(normal, uv)));
+                }
+                if corners.len() < 3 {
+                    return Err(syntax(line, format!("face with {} corners", corners.len())));
+                }
+                for i in 1..corners.len() - 1 {
+                    let (a, b, c) = (corners[0], corners[i], corners[i + 1]);
+                    tris.push(Tri3D::new_with_attrs(a.0, b.0, c.0, [a.1, b.1, c.1]));
+                    group.len += 1;
+                }
+            }
+            "o" | "g" | "usemtl" => {
+                if group.len > 0 {
+                    let start = group.start + group.len;
+                    groups.push(group.clone());
+                    group.start = start;
+                    group.len = 0;
+                }
+                match keyword {
+                    "o" => {
+                        group.object = rest();
+                        group.name = String::new();
+                    }
+                    "g" => group.name = rest(),
+                    _ => group.material = Some(rest()),
+                }
+            }
+            "mtllib" => material_libs.extend(tokens.map(|lib| lib.to_string())),
+            // smoothing groups, lines, points, free form geometry
+            _ => {}
+        }
+    }
+    if group.len > 0 {
+        groups.push(group);
+    }
+
+    let mut mesh = Mesh::from_tris(&tris);
+    mesh.groups = groups;
+    mesh.material_libs = material_libs;
+    Ok(mesh)
+}
+
+// Parses a Wavefront .mtl file, unknown statements are ignored
+pub fn load_mtl(bytes: &[u8]) -> Result<Vec<MtlMaterial>, ObjError> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (line, statement) in logical_lines(&text) {
+        let mut tokens = statement.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            materials.push(MtlMaterial::new(&name));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(syntax(line, format!("{:?} before newmtl", keyword))),
+        };
+
+        let mut value = [0.0; 3];
+        match keyword {
+            "Ka" | "Kd" | "Ks" | "Ke" => {
+                read_floats(line, tokens, 1, &mut value)?;
+                // a single value is a gray
+                if statement.split_whitespace().count() == 2 {
+                    value = [value[0]; 3];
+                }
+                match keyword {
+                    "Ka" => material.ambient = value,
+                    "Kd" => material.diffuse = value,
+                    "Ks" => material.specular = value,
+                    _ => material.emissive = value,
+                }
+            }
+            "Ns" => {
+                read_floats(line, tokens, 1, &mut value[..1])?;
+                material.shininess = value[0];
+            }
+            "d" => {
+                read_floats(line, tokens, 1, &mut value[..1])?;
+                material.opacity = value[0];
+            }
+            "Tr" => {
+                read_floats(line, tokens, 1, &mut value[..1])?;
+                material.opacity = 1.0 - value[0];
+            }
+            // options like -s or -bm come before the file name, which is last
+            "map_Kd" => material.diffuse_map = tokens.last().map(|file| file.to_string()),
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(mesh: &Mesh, corner: usize) -> (f64, f64, f64) {
+        let [a, b, c] = mesh.indices[corner / 3];
+        let p = mesh.vertices[[a, b, c][corner % 3] as usize].pos;
+        (p.x, p.y, p.z)
+    }
+
+    #[test]
+    fn negative_indices() {
+        let text = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0.5 0.25\nf -4/-1 -3/-1 -2/-1 -1/-1\n";
+        let mesh = load_obj(text.as_bytes()).unwrap();
+        // the quad is split in a fan from its first corner
+        assert_eq!(mesh.indices.len(), 2);
+        assert_eq!(pos(&mesh, 0), (0.0, 0.0, 0.0));
+        assert_eq!(pos(&mesh, 4), (1.0, 1.0, 0.0));
+        assert_eq!(pos(&mesh, 5), (0.0, 1.0, 0.0));
+        let uv = mesh.vertices[0].attr.uv;
+        assert_eq!((uv.x, uv.y), (0.5, 0.25));
+    }
+
+    #[test]
+    fn groups() {
+        let text = "mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+o box
+g front
+usemtl red
+f 1 2 3 4
+g back
+f 1 3 2
+usemtl blue
+f 1 4 3
+";
+        let mesh = load_obj(text.as_bytes()).unwrap();
+        assert_eq!(mesh.material_libs, vec!["scene.mtl".to_string()]);
+        let group = |object: &str, name: &str, material: &str, start, len| Group {
+            object: object.to_string(),
+            name: name.to_string(),
+            material: Some(material.to_string()),
+            material_id: None,
+            start,
+            len,
+        };
+        assert_eq!(
+            mesh.groups,
+            vec![
+                group("box", "front", "red", 0, 2),
+                group("box", "back", "red", 2, 1),
+                group("box", "back", "blue", 3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        let text = "v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 4\n";
+        assert_eq!(
+            load_obj(text.as_bytes()).unwrap_err(),
+            ObjError::Index { line: 4, index: 4 }
+        );
+        let text = "v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 -4\n";
+        assert_eq!(
+            load_obj(text.as_bytes()).unwrap_err(),
+            ObjError::Index { line: 4, index: -4 }
+        );
+        let text = "v 0 0 0\nv 1 0 0\nv 1 1 0\nf 0 1 2\n";
+        assert_eq!(
+            load_obj(text.as_bytes()).unwrap_err(),
+            ObjError::Index { line: 4, index: 0 }
+        );
+    }
+
+    #[test]
+    fn mtl() {
+        let text = "newmtl red\nKd 1 0 0\nKs 0.5\nd 0.25\nmap_Kd -s 2 2 2 red.png\n";
+        let materials = load_mtl(text.as_bytes()).unwrap();
+        assert_eq!(materials.len(), 1);
+        assert_eq!(materials[0].diffuse, [1.0, 0.0, 0.0]);
+        assert_eq!(materials[0].specular, [0.5; 3]);
+        assert_eq!(materials[0].opacity, 0.25);
+        assert_eq!(materials[0].diffuse_map.as_deref(), Some("red.png"));
+    }
+}
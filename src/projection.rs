@@ -0,0 +1,182 @@
+use std::{error, fmt};
+
+use wasm_bindgen::prelude::*;
+
+use crate::transforms::Mat3D;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectionKind {
+    Perspective = 0,
+    Orthographic = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectionError {
+    // perspective field of view outside (0, 180) degrees
+    Fov(f64),
+    // orthographic height that is not positive
+    Height(f64),
+    // near plane at or behind the eye
+    Near(f64),
+    // far plane not beyond the near one
+    Far { near: f64, far: f64 },
+}
+
+impl fmt::Display for ProjectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProjectionError::Fov(fov) => write!(
+                f,
+                "projection: field of view {} is not between 0 and 180 degrees",
+                fov
+            ),
+            ProjectionError::Height(height) => {
+                write!(f, "projection: height {} is not positive", height)
+            }
+            ProjectionError::Near(near) => {
+                write!(f, "projection: near plane {} is not positive", near)
+            }
+            ProjectionError::Far { near, far } => write!(
+                f,
+                "projection: far plane {} is not beyond the near plane {}",
+                far, near
+            ),
+        }
+    }
+}
+
+impl error::Error for ProjectionError {}
+
+// Camera lens: how view space is flattened to the screen.
+// The aspect ratio follows the render target, everything else is user settings.
+#[derive(Debug, Clone, Copy)]
+pub struct Projection {
+    pub kind: ProjectionKind,
+    // vertical field of view in degrees, perspective only
+    pub fov: f64,
+    // world units visible vertically, orthographic only
+    pub height: f64,
+    pub near: f64,
+    pub far: f64,
+    // screen height / screen width
+    pub aspect_ratio: f64,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection {
+            kind: ProjectionKind::Perspective,
+            fov: 90.0,
+            height: 2.0,
+            near: 0.1,
+            far: 1000.0,
+            aspect_ratio: 1.0,
+        }
+    }
+}
+
+impl Projection {
+    pub fn perspective(fov: f64, near: f64, far: f64) -> Projection {
+        Projection {
+            kind: ProjectionKind::Perspective,
+            fov,
+            near,
+            far,
+            ..Projection::default()
+        }
+    }
+
+    pub fn orthographic(height: f64, near: f64, far: f64) -> Projection {
+        Projection {
+            kind: ProjectionKind::Orthographic,
+            height,
+            near,
+            far,
+            ..Projection::default()
+        }
+    }
+
+    // the settings the matrix needs to be finite and keep the depth order, NaN and
+    // infinities fail too
+    pub fn check(&self) -> Result<(), ProjectionError> {
+        if !(self.near > 0.0 && self.near.is_finite()) {
+            return Err(ProjectionError::Near(self.near));
+        }
+        if !(self.far > self.near && self.far.is_finite()) {
+            return Err(ProjectionError::Far {
+                near: self.near,
+                far: self.far,
+            });
+        }
+        match self.kind {
+            ProjectionKind::Perspective if !(self.fov > 0.0 && self.fov < 180.0) => {
+                Err(ProjectionError::Fov(self.fov))
+            }
+            ProjectionKind::Orthographic if !(self.height > 0.0 && self.height.is_finite()) => {
+                Err(ProjectionError::Height(self.height))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // keeps square pixels for a target of this size
+    pub fn set_screen_size(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect_ratio = height as f64 / width as f64;
+        }
+    }
+
+    pub fn matrix(&self) -> Mat3D {
+        match self.kind {
+            ProjectionKind::Perspective => {
+                Mat3D::projection(self.fov, self.aspect_ratio, self.near, self.far)
+            }
+            ProjectionKind::Orthographic => {
+                Mat3D::orthographic(self.height, self.aspect_ratio, self.near, self.far)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check() {
+        assert_eq!(Projection::default().check(), Ok(()));
+        // only the settings of the kind in use count
+        let projection = Projection {
+            fov: 0.0,
+            ..Projection::orthographic(5.0, 0.1, 10.0)
+        };
+        assert_eq!(projection.check(), Ok(()));
+
+        for fov in [0.0, -10.0, 180.0, 270.0, f64::NAN] {
+            let projection = Projection::perspective(fov, 0.1, 10.0);
+            assert!(matches!(projection.check(), Err(ProjectionError::Fov(_))));
+        }
+        for height in [0.0, -1.0, f64::INFINITY] {
+            let projection = Projection::orthographic(height, 0.1, 10.0);
+            assert!(matches!(
+                projection.check(),
+                Err(ProjectionError::Height(_))
+            ));
+        }
+        assert_eq!(
+            Projection::perspective(60.0, 0.0, 10.0).check(),
+            Err(ProjectionError::Near(0.0))
+        );
+        assert_eq!(
+            Projection::perspective(60.0, 1.0, 1.0).check(),
+            Err(ProjectionError::Far {
+                near: 1.0,
+                far: 1.0
+            })
+        );
+        assert!(Projection::orthographic(1.0, 1.0, f64::NAN)
+            .check()
+            .is_err());
+    }
+}
@@ -0,0 +1,576 @@
+use wasm_bindgen::prelude::*;
+
+use crate::framebuffer::{Color, Framebuffer, BLACK};
+use crate::tri3D::Tri3D;
+use crate::vec3D::Vec3D;
+
+// Side of the triangles that is not drawn
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+    // both sides, for models with inconsistent winding
+    None = 0,
+    Back = 1,
+    Front = 2,
+}
+
+// Order of the corners of a front facing triangle as seen from the camera, which is
+// also their order on screen
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Winding {
+    Clockwise = 0,
+    CounterClockwise = 1,
+}
+
+// What is drawn of each triangle
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    // filled, with the edges of the visible surfaces drawn over in the line style
+    Outlined = 0,
+    // filled only
+    Solid = 1,
+    // every edge, the hidden ones too
+    Wireframe = 2,
+    // edges of the visible surfaces only
+    HiddenLine = 3,
+    // the vertices only
+    Points = 4,
+}
+
+impl RenderMode {
+    // modes showing surfaces, the others draw every triangle whatever its side
+    pub fn fills(self) -> bool {
+        matches!(
+            self,
+            RenderMode::Outlined | RenderMode::Solid | RenderMode::HiddenLine
+        )
+    }
+}
+
+// How triangles are turned into pixels
+#[derive(Debug, Clone, Copy)]
+pub struct RasterState {
+    pub mode: RenderMode,
+    pub cull: CullMode,
+    pub front_face: Winding,
+    // back faces are lit as if their normals pointed to the camera, otherwise they
+    // are lit from the front side and show dark. Also fixes normals pointing inside
+    // on models with inconsistent winding
+    pub two_sided: bool,
+}
+
+impl Default for RasterState {
+    fn default() -> Self {
+        RasterState {
+            mode: RenderMode::Outlined,
+            cull: CullMode::Back,
+            front_face: Winding::Clockwise,
+            two_sided: false,
+        }
+    }
+}
+
+// Where the camera sees the triangles from, to tell which of their sides is visible
+#[derive(Debug, Clone, Copy)]
+pub enum Viewer {
+    // perspective projection: every view ray leaves the eye
+    Eye(Vec3D),
+    // orthographic projection: every view ray is parallel to the camera forward
+    Direction(Vec3D),
+}
+
+impl Viewer {
+    // direction of the view ray reaching `p`, not normalized
+    pub fn ray_to(&self, p: &Vec3D) -> Vec3D {
+        match self {
+            Viewer::Eye(eye) => *p - *eye,
+            Viewer::Direction(forward) => *forward,
+        }
+    }
+}
+
+impl RasterState {
+    // unit normal on the front side of a world space triangle, zero when degenerate
+    pub fn front_normal(&self, tri: &Tri3D) -> Vec3D {
+        let normal = (tri[1] - tri[0]).cross_product(tri[2] - tri[0]);
+        if normal.length() == 0.0 {
+            return normal;
+        }
+        match self.front_face {
+            Winding::Clockwise => normal.normalized(),
+            Winding::CounterClockwise => normal.normalized().scale(-1.0),
+        }
+    }
+
+    // true when the front side of the triangle is towards the viewer
+    pub fn is_front_facing(&self, tri: &Tri3D, viewer: &Viewer) -> bool {
+        self.front_normal(tri).dot_product(viewer.ray_to(&tri[0])) < 0.0
+    }
+
+    pub fn is_culled(&self, tri: &Tri3D, viewer: &Viewer) -> bool {
+        match self.cull {
+            CullMode::None => false,
+            CullMode::Back => !self.is_front_facing(tri, viewer),
+            CullMode::Front => self.is_front_facing(tri, viewer),
+        }
+    }
+}
+
+// how far in depth a line may be behind the surfaces and still show, so the edges
+// of a triangle are not hidden by the triangle itself
+pub const LINE_DEPTH_BIAS: f64 = 1e-5;
+
+// How lines are drawn
+#[derive(Debug, Clone, Copy)]
+pub struct LineStyle {
+    pub color: Color,
+    // across the line, in pixels
+    pub width: f64,
+    // Xiaolin Wu smooth borders instead of Bresenham hard steps
+    pub antialiased: bool,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        LineStyle {
+            color: BLACK,
+            width: 1.0,
+            antialiased: false,
+        }
+    }
+}
+
+// A pixel covered by the triangle being rasterized
+pub struct Fragment {
+    pub x: u32,
+    pub y: u32,
+    pub z: f64,
+    // barycentric weights of tri[0], tri[1] and tri[2] on screen, they sum to 1
+    pub bary: [f64; 3],
+    // same weights corrected for perspective with the corners 1 / w, to interpolate
+    // vertex attributes (uv, normals...) without them swimming across the triangle
+    pub perspective: [f64; 3],
+    // change of `perspective` to the next pixel right and down, gives the screen space
+    // derivatives of any attribute (texture level of detail)
+    pub dx: [f64; 3],
+    pub dy: [f64; 3],
+}
+
+// > 0 when a -> b -> p turns clockwise on screen (y pointing down)
+fn edge(a: &Vec3D, b: &Vec3D, px: f64, py: f64) -> f64 {
+    (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x)
+}
+
+// top-left fill rule: pixels exactly on a shared edge belong to only one triangle
+fn is_top_left(a: &Vec3D, b: &Vec3D) -> bool {
+    let top = a.y == b.y && b.x > a.x;
+    let left = b.y < a.y;
+    top || left
+}
+
+// Writes what `shade` returns for each fragment of the triangle in front of the depth
+// buffer. Returning None from `shade` discards the fragment. Opaque colors also write
+// their depth, the others are blended over the framebuffer and leave the depth as it is,
+// so they do not hide what is drawn behind them afterwards.
+// Vertices are in screen space: x, y in pixels, z is the depth with smaller being closer.
+pub fn fill_tri<F>(framebuffer: &mut Framebuffer, tri: &Tri3D, mut shade: F)
+where
+    F: FnMut(&Fragment) -> Option<Color>,
+{
+    rasterize(framebuffer, tri, |framebuffer, fragment| {
+        let (x, y) = (fragment.x, fragment.y);
+        match shade(fragment) {
+            Some(color) if color[3] == 255 => {
+                framebuffer.set_depth(x, y, fragment.z);
+                framebuffer.set_pixel(x as i64, y as i64, color);
+            }
+            Some(color) => framebuffer.blend_pixel(x as i64, y as i64, color),
+            None => {}
+        }
+    });
+}
+
+// only the depth of the triangle, where it is in front of the depth buffer
+pub fn fill_depth(framebuffer: &mut Framebuffer, tri: &Tri3D) {
+    rasterize(framebuffer, tri, |framebuffer, fragment| {
+        framebuffer.set_depth(fragment.x, fragment.y, fragment.z)
+    });
+}
+
+// Walks every pixel center of the triangle bounding box with edge functions and calls
+// `visit` for the ones in front of the framebuffer depth buffer
+fn rasterize<F>(framebuffer: &mut Framebuffer, tri: &Tri3D, mut visit: F)
+where
+    F: FnMut(&mut Framebuffer, &Fragment),
+{
+    let (mut v0, mut v1, v2) = (tri[0], tri[1], tri[2]);
+    let mut order = [0, 1, 2];
+
+    let mut area = edge(&v0, &v1, v2.x, v2.y);
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+    // wind every triangle the same way so the inside test is the same for all
+    if area < 0.0 {
+        std::mem::swap(&mut v0, &mut v1);
+        order.swap(0, 1);
+        area = -area;
+    }
+
+    let scissor = framebuffer.scissor();
+    let min_x = v0.x.min(v1.x).min(v2.x).floor().max(scissor.x as f64);
+    let min_y = v0.y.min(v1.y).min(v2.y).floor().max(scissor.y as f64);
+    let max_x =
+        v0.x.max(v1.x)
+            .max(v2.x)
+            .ceil()
+            .min((scissor.x + scissor.width) as f64 - 1.0);
+    let max_y =
+        v0.y.max(v1.y)
+            .max(v2.y)
+            .ceil()
+            .min((scissor.y + scissor.height) as f64 - 1.0);
+    if min_x > max_x || min_y > max_y {
+        return;
+    }
+
+    // attributes divided by w are linear on screen
+    let inv_w = [v0.w, v1.w, v2.w].map(|w| {
+        if w.is_finite() && w.abs() > 1e-12 {
+            1.0 / w
+        } else {
+            1.0
+        }
+    });
+
+    // edge i is opposite to vertex i
+    let edges = [(&v1, &v2), (&v2, &v0), (&v0, &v1)];
+    let bias = edges.map(|(a, b)| if is_top_left(a, b) { 0.0 } else { -1e-9 });
+
+    // perspective correct weights anywhere on screen, in the caller vertex order
+    let perspective_at = |px: f64, py: f64| -> [f64; 3] {
+        let b = edges.map(|(a, b)| edge(a, b, px, py) / area);
+        let p = [b[0] * inv_w[0], b[1] * inv_w[1], b[2] * inv_w[2]];
+        let sum = p[0] + p[1] + p[2];
+        let mut res = [0.0; 3];
+        for i in 0..3 {
+            res[order[i]] = if sum != 0.0 { p[i] / sum } else { b[i] };
+        }
+        res
+    };
+
+    for y in (min_y as u32)..=(max_y as u32) {
+        let py = y as f64 + 0.5;
+        for x in (min_x as u32)..=(max_x as u32) {
+            let px = x as f64 + 0.5;
+            let w = [
+                edge(edges[0].0, edges[0].1, px, py),
+                edge(edges[1].0, edges[1].1, px, py),
+                edge(edges[2].0, edges[2].1, px, py),
+            ];
+            if w[0] + bias[0] < 0.0 || w[1] + bias[1] < 0.0 || w[2] + bias[2] < 0.0 {
+                continue;
+            }
+
+            let b = [w[0] / area, w[1] / area, w[2] / area];
+            let z = b[0] * v0.z + b[1] * v1.z + b[2] * v2.z;
+            if z >= framebuffer.depth(x, y) {
+                continue;
+            }
+
+            // back to the caller vertex order
+            let mut bary = [0.0; 3];
+            bary[order[0]] = b[0];
+            bary[order[1]] = b[1];
+            bary[order[2]] = b[2];
+
+            let perspective = perspective_at(px, py);
+            let (right, down) = (perspective_at(px + 1.0, py), perspective_at(px, py + 1.0));
+
+            let fragment = Fragment {
+                x,
+                y,
+                z,
+                bary,
+                perspective,
+                dx: [0, 1, 2].map(|i| right[i] - perspective[i]),
+                dy: [0, 1, 2].map(|i| down[i] - perspective[i]),
+            };
+            visit(framebuffer, &fragment);
+        }
+    }
+}
+
+// Largest change of depth from one pixel to the next across a screen space triangle
+pub fn depth_slope(tri: &Tri3D) -> f64 {
+    let (a, b, c) = (tri[0], tri[1], tri[2]);
+    let det = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+    if det == 0.0 || !det.is_finite() {
+        return 0.0;
+    }
+    let dz_dx = ((b.z - a.z) * (c.y - a.y) - (c.z - a.z) * (b.y - a.y)) / det;
+    let dz_dy = ((c.z - a.z) * (b.x - a.x) - (b.z - a.z) * (c.x - a.x)) / det;
+    dz_dx.abs().max(dz_dy.abs())
+}
+
+// Draws the segment a -> b in screen space (x, y in pixels, z the depth). With
+// `depth_test` pixels behind the depth buffer are skipped, lines never write depth
+// so they can be drawn over the surfaces they outline.
+pub fn draw_line(
+    framebuffer: &mut Framebuffer,
+    a: &Vec3D,
+    b: &Vec3D,
+    style: &LineStyle,
+    depth_test: bool,
+) {
+    if ![a.x, a.y, b.x, b.y].iter().all(|v| v.is_finite()) {
+        return;
+    }
+    let scissor = framebuffer.scissor();
+    // `coverage` is the part of the pixel under the line
+    let mut plot = |x: i64, y: i64, z: f64, coverage: f64| {
+        if coverage <= 0.0 || !scissor.contains(x, y) {
+            return;
+        }
+        if depth_test && z - LINE_DEPTH_BIAS >= framebuffer.depth(x as u32, y as u32) {
+            return;
+        }
+        let mut color = style.color;
+        if coverage >= 1.0 && color[3] == 255 {
+            framebuffer.set_pixel(x, y, color);
+        } else {
+            color[3] = (color[3] as f64 * coverage.min(1.0)).round() as u8;
+            framebuffer.blend_pixel(x, y, color);
+        }
+    };
+    if style.antialiased {
+        wu_line(a, b, style.width, &mut plot);
+    } else {
+        bresenham_line(a, b, style.width, &mut plot);
+    }
+}
+
+// Integer steps from the pixel of a to the pixel of b, thick lines repeat each pixel
+// across the minor axis
+fn bresenham_line<F>(a: &Vec3D, b: &Vec3D, width: f64, plot: &mut F)
+where
+    F: FnMut(i64, i64, f64, f64),
+{
+    let (mut x, mut y) = (a.x.floor() as i64, a.y.floor() as i64);
+    let (x1, y1) = (b.x.floor() as i64, b.y.floor() as i64);
+    let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+    let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+    let steps = dx.max(-dy).max(1) as f64;
+
+    let thickness = width.round().max(1.0) as i64;
+    // offsets go across the line: along y when it is mostly horizontal
+    let (ox, oy) = if dx >= -dy { (0, 1) } else { (1, 0) };
+    let offsets = -(thickness - 1) / 2..=thickness / 2;
+
+    let mut err = dx + dy;
+    let mut step = 0.0;
+    loop {
+        let z = a.z + (b.z - a.z) * (step / steps);
+        for o in offsets.clone() {
+            plot(x + o * ox, y + o * oy, z, 1.0);
+        }
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+        step += 1.0;
+    }
+}
+
+// Xiaolin Wu: along the major axis, each column of pixels gets the part of it covered
+// by the line, here a band `width` pixels thick, ends included
+fn wu_line<F>(a: &Vec3D, b: &Vec3D, width: f64, plot: &mut F)
+where
+    F: FnMut(i64, i64, f64, f64),
+{
+    let steep = (b.y - a.y).abs() > (b.x - a.x).abs();
+    // (major, minor, depth)
+    let swap = |p: &Vec3D| {
+        if steep {
+            (p.y, p.x, p.z)
+        } else {
+            (p.x, p.y, p.z)
+        }
+    };
+    let (mut p0, mut p1) = (swap(a), swap(b));
+    if p0.0 > p1.0 {
+        std::mem::swap(&mut p0, &mut p1);
+    }
+    let du = p1.0 - p0.0;
+    if du <= 0.0 {
+        return;
+    }
+    let gradient = (p1.1 - p0.1) / du;
+    // the band is thicker along the minor axis when the line is slanted
+    let half = 0.5 * width.max(0.0) * (1.0 + gradient * gradient).sqrt();
+
+    for u in (p0.0.floor() as i64)..=(p1.0.floor() as i64) {
+        let cover_u = ((u + 1) as f64).min(p1.0) - (u as f64).max(p0.0);
+        let center = (u as f64 + 0.5).clamp(p0.0, p1.0);
+        let t = (center - p0.0) / du;
+        let v = p0.1 + gradient * (center - p0.0);
+        let z = p0.2 + (p1.2 - p0.2) * t;
+        let (low, high) = (v - half, v + half);
+        for r in (low.floor() as i64)..=(high.floor() as i64) {
+            let cover_v = ((r + 1) as f64).min(high) - (r as f64).max(low);
+            let coverage = cover_u.clamp(0.0, 1.0) * cover_v.clamp(0.0, 1.0);
+            if steep {
+                plot(r, u, z, coverage);
+            } else {
+                plot(u, r, z, coverage);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::TRANSPARENT;
+
+    const RED: Color = [255, 0, 0, 255];
+    const GREEN: Color = [0, 255, 0, 255];
+
+    fn framebuffer(width: u32, height: u32) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(width, height).unwrap();
+        framebuffer.clear(TRANSPARENT);
+        framebuffer.clear_depth();
+        framebuffer
+    }
+
+    // screen space triangle at a constant depth
+    fn tri(points: [(f64, f64); 3], z: f64) -> Tri3D {
+        let [a, b, c] = points.map(|(x, y)| Vec3D::new(x, y, z));
+        Tri3D::new(a, b, c)
+    }
+
+    #[test]
+    fn shared_edge_drawn_once() {
+        let mut framebuffer = framebuffer(8, 8);
+        let mut hits = [0; 64];
+        // a square cut along its diagonal, then a triangle sharing its horizontal edge
+        let tris = [
+            tri([(0.0, 0.0), (8.0, 0.0), (8.0, 8.0)], 0.5),
+            tri([(0.0, 0.0), (8.0, 8.0), (0.0, 8.0)], 0.5),
+        ];
+        for tri in tris.iter() {
+            fill_tri(&mut framebuffer, tri, |fragment| {
+                hits[(fragment.y * 8 + fragment.x) as usize] += 1;
+                None
+            });
+        }
+        assert!(hits.iter().all(|&n| n == 1), "{:?}", hits);
+
+        let mut hits = [0; 64];
+        let tris = [
+            tri([(1.0, 1.0), (7.0, 4.0), (1.0, 4.0)], 0.5),
+            tri([(1.0, 4.0), (7.0, 4.0), (4.0, 7.5)], 0.5),
+        ];
+        for tri in tris.iter() {
+            fill_tri(&mut framebuffer, tri, |fragment| {
+                hits[(fragment.y * 8 + fragment.x) as usize] += 1;
+                None
+            });
+        }
+        assert!(hits.iter().all(|&n| n <= 1), "{:?}", hits);
+        // the row of centers exactly on the shared edge y = 4 is covered
+        assert!(
+            hits[4 * 8 + 2..4 * 8 + 6].iter().all(|&n| n == 1),
+            "{:?}",
+            hits
+        );
+    }
+
+    #[test]
+    fn nearer_wins() {
+        let points = [(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)];
+        for &(first, second) in [(0.2, 0.7), (0.7, 0.2)].iter() {
+            let mut framebuffer = framebuffer(4, 4);
+            let color = |z: f64| if z < 0.5 { RED } else { GREEN };
+            fill_tri(&mut framebuffer, &tri(points, first), |_| {
+                Some(color(first))
+            });
+            fill_tri(&mut framebuffer, &tri(points, second), |_| {
+                Some(color(second))
+            });
+            assert_eq!(framebuffer.get_pixel(0, 0), RED);
+            assert_eq!(framebuffer.depth(0, 0), 0.2);
+        }
+    }
+
+    #[test]
+    fn perspective_weights() {
+        let mut framebuffer = framebuffer(16, 16);
+        let mut t = tri([(0.0, 0.0), (16.0, 2.0), (3.0, 16.0)], 0.5);
+        // corners at different distances
+        t.a.w = 1.0;
+        t.b.w = 4.0;
+        t.c.w = 10.0;
+        let mut count = 0;
+        fill_tri(&mut framebuffer, &t, |fragment| {
+            count += 1;
+            for weights in [fragment.bary, fragment.perspective].iter() {
+                assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+                assert!(weights.iter().all(|&w| w >= -1e-9));
+            }
+            // the far corners weigh less than on screen
+            assert!(fragment.perspective[0] >= fragment.bary[0] - 1e-9);
+            None
+        });
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn nothing_drawn() {
+        let mut framebuffer = framebuffer(8, 8);
+        let mut count = 0;
+        let tris = [
+            // no area
+            tri([(0.0, 0.0), (4.0, 4.0), (8.0, 8.0)], 0.5),
+            tri([(2.0, 2.0), (2.0, 2.0), (2.0, 2.0)], 0.5),
+            // outside of the framebuffer
+            tri([(-10.0, -10.0), (-2.0, -10.0), (-10.0, -2.0)], 0.5),
+            tri([(9.0, 0.0), (20.0, 0.0), (9.0, 8.0)], 0.5),
+            tri([(f64::NAN, 0.0), (4.0, 0.0), (0.0, 4.0)], 0.5),
+        ];
+        for tri in tris.iter() {
+            fill_tri(&mut framebuffer, tri, |_| {
+                count += 1;
+                Some(RED)
+            });
+        }
+        assert_eq!(count, 0);
+        assert!(framebuffer.pixels().iter().all(|&v| v == 0));
+    }
+
+    #[test]
+    fn translucent_keeps_depth() {
+        let mut framebuffer = framebuffer(4, 4);
+        let square = [(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)];
+        fill_tri(&mut framebuffer, &tri(square, 0.2), |_| {
+            Some([255, 0, 0, 128])
+        });
+        // drawn later but behind, still shows through
+        fill_tri(&mut framebuffer, &tri(square, 0.5), |_| Some(GREEN));
+        assert_eq!(framebuffer.get_pixel(0, 0), GREEN);
+
+        fill_depth(&mut framebuffer, &tri(square, 0.1));
+        fill_tri(&mut framebuffer, &tri(square, 0.2), |_| Some(RED));
+        assert_eq!(framebuffer.get_pixel(0, 0), GREEN);
+    }
+}
@@ -1,285 +1,1290 @@
+use std::collections::HashSet;
 use std::f64;
 
 extern crate console_error_panic_hook;
-use std::panic;
 
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
 
-use crate::camera::Camera;
+// triangles are only cut at the screen edges once they reach twice the viewport size
+const GUARD_BAND: f64 = 2.0;
+// side in pixels of the squares drawn in RenderMode::Points
+const POINT_SIZE: i64 = 2;
+
+use crate::animation::{Channel, Interpolation, Keyframe, Timeline};
+use crate::bounds::Frustum;
+use crate::camera::{Camera, CameraMode};
+use crate::clip;
+use crate::framebuffer::{Color, Framebuffer, FramebufferError, Rect, Viewport, TRANSPARENT};
+use crate::graph::{SceneGraph, Transform};
+use crate::image;
+use crate::input::Input;
+use crate::light::{Light, LightKind, Lighting, ShadingMode};
 use crate::loader;
+use crate::material::Material;
+use crate::mesh::{Mesh, CREASE_ANGLE};
+use crate::obj;
+use crate::projection::{Projection, ProjectionError, ProjectionKind};
+use crate::rasterizer::{self, CullMode, LineStyle, RasterState, RenderMode, Viewer, Winding};
+use crate::target::{Canvas, Headless, RenderTarget};
+use crate::texture::{Filter, Texture, Wrap};
 use crate::transforms::Mat3D;
 use crate::tri3D::Tri3D;
 use crate::vec3D::Vec3D;
+use crate::vertex::VertexAttr;
+
 #[wasm_bindgen]
 pub struct Scene {
-    canvas: web_sys::HtmlCanvasElement, // width and height, framebuffer
-    ctx: web_sys::CanvasRenderingContext2d,
-    // list of meshs || list of triangles
-    tris: Vec<Tri3D>,
-    camera: Camera,           // Word view to camera view
-    projection_matrix: Mat3D, // 3D to 2D
+    target: Box<dyn RenderTarget>, // canvas or headless, receives the framebuffer
+    framebuffer: Framebuffer,
+    graph: SceneGraph, // every model and where it is
+    animation: Timeline,
+    camera: Camera, // Word view to camera view
+    input: Input,
+    lighting: Lighting,
+    shading: ShadingMode,
+    raster: RasterState,       // face culling and winding
+    line_style: LineStyle,     // wireframe and hidden line edges
+    projection: Projection,    // lens settings
+    projection_matrix: Mat3D,  // 3D to 2D, rebuilt when the projection or viewport changes
+    viewport: Viewport,        // part of the target rendered to
+    scissor: Option<Viewport>, // pixels outside of it are left untouched
+    guard_band: f64,           // clip space margin before triangles get cut
 }
 
 #[wasm_bindgen]
 impl Scene {
-    pub fn new(canvas: web_sys::HtmlCanvasElement) -> Scene {
+    // node 0 holds the model
+    pub fn new(canvas: web_sys::HtmlCanvasElement) -> Result<Scene, JsValue> {
         console_error_panic_hook::set_once();
-        let ctx = canvas
-            .get_context("2d")
-            .unwrap()
-            .unwrap()
-            .dyn_into::<web_sys::CanvasRenderingContext2d>()
-            .unwrap();
-        ctx.set_image_smoothing_enabled(true);
-        let screen_width = canvas.width() as f64;
-        let screen_height = canvas.width() as f64;
-        Scene {
-            canvas,
-            ctx,
-            tris: Vec::from([
-                // south
-                Tri3D::from_points(0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0),
-                Tri3D::from_points(0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0),
-                // east
-                Tri3D::from_points(1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0),
-                Tri3D::from_points(1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0),
-                // north
-                Tri3D::from_points(1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0),
-                Tri3D::from_points(1.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0),
-                // west
-                Tri3D::from_points(0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0),
-                Tri3D::from_points(0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0),
-                // top
-                Tri3D::from_points(0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0),
-                Tri3D::from_points(0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0),
-                // bottom
-                Tri3D::from_points(1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0),
-                Tri3D::from_points(1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0),
-            ]),
-            camera: Camera::new(),
-            projection_matrix: Mat3D::projection(90.0, screen_height / screen_width, 0.1, 1000.0),
-        }
+        Scene::with_target(Box::new(Canvas::new(canvas)), Scene::cube())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
     }
 
-    pub fn new_teapot(canvas: web_sys::HtmlCanvasElement) -> Scene {
+    // node 0 holds the model
+    pub fn new_teapot(canvas: web_sys::HtmlCanvasElement) -> Result<Scene, JsValue> {
         console_error_panic_hook::set_once();
-        let ctx = canvas
-            .get_context("2d")
-            .unwrap()
-            .unwrap()
-            .dyn_into::<web_sys::CanvasRenderingContext2d>()
-            .unwrap();
-        ctx.set_image_smoothing_enabled(true);
-        let screen_width = canvas.width() as f64;
-        let screen_height = canvas.width() as f64;
-        Scene {
-            canvas,
-            ctx,
-            tris: Vec::from(loader::load_teapot()),
-            camera: Camera::new(),
-            projection_matrix: Mat3D::projection(90.0, screen_height / screen_width, 0.1, 1000.0),
+        Scene::with_target(Box::new(Canvas::new(canvas)), loader::load_teapot())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    // ascii or binary stl file, returns a mesh id for add_node
+    pub fn load_stl(&mut self, bytes: &[u8]) -> Result<u32, JsValue> {
+        let mesh = loader::load_stl(bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(self.graph.add_mesh(mesh) as u32)
+    }
+
+    // wavefront obj file, returns a mesh id for add_node
+    pub fn load_obj(&mut self, bytes: &[u8]) -> Result<u32, JsValue> {
+        let mesh = obj::load_obj(bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(self.graph.add_mesh(mesh) as u32)
+    }
+
+    // wavefront mtl file, returns the ids of its materials in file order
+    pub fn load_mtl(&mut self, bytes: &[u8]) -> Result<Vec<u32>, JsValue> {
+        let materials = obj::load_mtl(bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(materials
+            .iter()
+            .map(|mtl| self.graph.add_material(Material::from(mtl)) as u32)
+            .collect())
+    }
+
+    // wavefront mtl file named by the mtllib of an obj mesh, its groups take the
+    // materials of their usemtl, returns the ids of the materials in file order
+    pub fn load_mesh_mtl(&mut self, mesh: u32, bytes: &[u8]) -> Result<Vec<u32>, JsValue> {
+        let library = obj::load_mtl(bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(self
+            .link_materials(mesh as usize, &library)
+            .into_iter()
+            .map(|id| id as u32)
+            .collect())
+    }
+
+    // new node at the origin of its parent (or of the world), returns its id
+    pub fn add_node(&mut self, parent: Option<u32>, mesh: Option<u32>) -> u32 {
+        self.graph
+            .add_node(parent.map(|p| p as usize), mesh.map(|m| m as usize)) as u32
+    }
+
+    pub fn remove_node(&mut self, node: u32) {
+        self.graph.remove_node(node as usize);
+    }
+
+    pub fn set_parent(&mut self, node: u32, parent: Option<u32>) {
+        self.graph
+            .set_parent(node as usize, parent.map(|p| p as usize));
+    }
+
+    pub fn set_visible(&mut self, node: u32, visible: bool) {
+        if let Some(node) = self.graph.node_mut(node as usize) {
+            node.visible = visible;
         }
     }
 
+    pub fn set_translation(&mut self, node: u32, x: f64, y: f64, z: f64) {
+        if let Some(node) = self.graph.node_mut(node as usize) {
+            node.transform.translation = Vec3D::new(x, y, z);
+        }
+    }
+
+    // radians around x, then y, then z
+    pub fn set_rotation(&mut self, node: u32, x: f64, y: f64, z: f64) {
+        if let Some(node) = self.graph.node_mut(node as usize) {
+            node.transform.rotation = Vec3D::new(x, y, z);
+        }
+    }
+
+    pub fn set_scale(&mut self, node: u32, x: f64, y: f64, z: f64) {
+        if let Some(node) = self.graph.node_mut(node as usize) {
+            node.transform.scale = Vec3D::new(x, y, z);
+        }
+    }
+
+    // white plastic material, returns its id for the set_material_* methods
+    pub fn add_material(&mut self) -> u32 {
+        self.graph.add_material(Material::default()) as u32
+    }
+
+    // colors are linear RGB in [0, 1]
+    pub fn set_material_diffuse(&mut self, material: u32, r: f64, g: f64, b: f64) {
+        if let Some(material) = self.graph.material_mut(material as usize) {
+            material.diffuse = [r, g, b];
+        }
+    }
+
+    pub fn set_material_specular(&mut self, material: u32, r: f64, g: f64, b: f64, shininess: f64) {
+        if let Some(material) = self.graph.material_mut(material as usize) {
+            material.specular = [r, g, b];
+            material.shininess = shininess;
+        }
+    }
+
+    pub fn set_material_emissive(&mut self, material: u32, r: f64, g: f64, b: f64) {
+        if let Some(material) = self.graph.material_mut(material as usize) {
+            material.emissive = [r, g, b];
+        }
+    }
+
+    // 1 is opaque, 0 invisible
+    pub fn set_material_opacity(&mut self, material: u32, opacity: f64) {
+        if let Some(material) = self.graph.material_mut(material as usize) {
+            material.opacity = opacity;
+        }
+    }
+
+    // default material of every node showing the mesh
+    pub fn set_mesh_material(&mut self, mesh: u32, material: Option<u32>) {
+        self.graph
+            .set_mesh_material(mesh as usize, material.map(|m| m as usize));
+    }
+
+    // png or ppm image, returns a texture id for set_material_texture
+    pub fn load_texture(&mut self, bytes: &[u8]) -> Result<u32, JsValue> {
+        let texture = Texture::decode(bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(self.graph.add_texture(texture) as u32)
+    }
+
+    // anisotropy: most samples along a stretched pixel footprint, 1 to disable
+    pub fn set_texture_sampling(
+        &mut self,
+        texture: u32,
+        filter: Filter,
+        wrap: Wrap,
+        anisotropy: u32,
+    ) {
+        if let Some(texture) = self.graph.texture_mut(texture as usize) {
+            texture.filter = filter;
+            texture.wrap = wrap;
+            texture.anisotropy = anisotropy.max(1);
+        }
+    }
+
+    // image multiplying the diffuse color and opacity, mapped with the mesh uvs
+    pub fn set_material_texture(&mut self, material: u32, texture: Option<u32>) {
+        if let Some(material) = self.graph.material_mut(material as usize) {
+            material.texture = texture.map(|t| t as usize);
+        }
+    }
+
+    // overrides the mesh material, also used by children that have none
+    pub fn set_node_material(&mut self, node: u32, material: Option<u32>) {
+        if let Some(node) = self.graph.node_mut(node as usize) {
+            node.material = material.map(|m| m as usize);
+        }
+    }
+
+    // value of a node translation, rotation (radians) or scale at `time` seconds of the
+    // animation, `interpolation` leads to the next keyframe
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_keyframe(
+        &mut self,
+        node: u32,
+        channel: Channel,
+        time: f64,
+        x: f64,
+        y: f64,
+        z: f64,
+        interpolation: Interpolation,
+    ) {
+        let key = Keyframe::new(time, Vec3D::new(x, y, z), interpolation);
+        self.animation.add_keyframe(node as usize, channel, key);
+    }
+
+    pub fn clear_animation(&mut self) {
+        self.animation.clear();
+    }
+
+    pub fn play_animation(&mut self) {
+        self.animation.playing = true;
+    }
+
+    pub fn pause_animation(&mut self) {
+        self.animation.playing = false;
+    }
+
+    // restart from the beginning once the last keyframe is reached
+    pub fn set_animation_loop(&mut self, looping: bool) {
+        self.animation.looping = looping;
+    }
+
+    // 1 is real time, 0.5 half speed
+    pub fn set_animation_speed(&mut self, speed: f64) {
+        self.animation.speed = speed;
+    }
+
+    // moves the animation to `time` seconds and poses the nodes right away, so the next
+    // render shows that frame even without a tick
+    pub fn seek_animation(&mut self, time: f64) {
+        self.animation.seek(time);
+        self.animation.apply(&mut self.graph);
+    }
+
+    pub fn animation_time(&self) -> f64 {
+        self.animation.time()
+    }
+
+    pub fn animation_duration(&self) -> f64 {
+        self.animation.duration()
+    }
+
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        self.camera.set_mode(mode);
+    }
+
+    pub fn set_camera_position(&mut self, x: f64, y: f64, z: f64) {
+        self.camera.pos = Vec3D::new(x, y, z);
+    }
+
+    // world up vector the camera angles are measured against
+    pub fn set_camera_up(&mut self, x: f64, y: f64, z: f64) {
+        self.camera.up = Vec3D::new(x, y, z);
+    }
+
+    // absolute angles in radians, ignored in free mode
+    pub fn set_camera_angles(&mut self, yaw: f64, pitch: f64, roll: f64) {
+        self.camera.yaw = yaw;
+        self.camera.pitch = pitch;
+        self.camera.roll = roll;
+    }
+
+    // increments in radians around the camera own axes
+    pub fn rotate_camera(&mut self, yaw: f64, pitch: f64, roll: f64) {
+        self.camera.rotate(yaw, pitch, roll);
+    }
+
+    pub fn camera_look_at(&mut self, x: f64, y: f64, z: f64) {
+        self.camera.look_at(&Vec3D::new(x, y, z));
+    }
+
+    // switch to orbit mode around a point
+    pub fn camera_orbit(&mut self, x: f64, y: f64, z: f64, distance: f64) {
+        self.camera.orbit(&Vec3D::new(x, y, z), distance);
+    }
+
+    // Moves the camera along its view direction (or sets the orbit center and distance)
+    // so the bounding sphere of a node and its children just fits the view, the far
+    // plane is pushed back when it would cut it. False when they have no vertices.
+    pub fn frame_node(&mut self, node: u32) -> bool {
+        let sphere = match self.graph.node_bounds(node as usize) {
+            Some(bounds) => bounds.sphere,
+            None => return false,
+        };
+        let radius = sphere.radius.max(1e-6);
+        let distance = match self.projection.kind {
+            ProjectionKind::Perspective => {
+                // the narrowest of the vertical and horizontal fields of view
+                let half = (self.projection.fov.to_radians() * 0.5).tan();
+                let half = half.min(half / self.projection.aspect_ratio).atan();
+                radius / half.sin()
+            }
+            ProjectionKind::Orthographic => {
+                self.projection.height = 2.0 * radius * self.projection.aspect_ratio.max(1.0);
+                self.projection.near + 2.0 * radius
+            }
+        };
+        self.projection.far = self.projection.far.max(2.0 * (distance + radius));
+        self.update_projection();
+
+        self.camera.compute_view();
+        if self.camera.mode == CameraMode::Orbit {
+            self.camera.target = sphere.center;
+            self.camera.distance = distance;
+        } else {
+            self.camera.pos = sphere.center - self.camera.look_dir.scale(distance);
+        }
+        true
+    }
+
+    // fov in degrees, in (0, 180), and 0 < near < far. Nothing changes otherwise
+    pub fn set_perspective(&mut self, fov: f64, near: f64, far: f64) -> Result<(), JsValue> {
+        self.set_projection(Projection {
+            kind: ProjectionKind::Perspective,
+            fov,
+            near,
+            far,
+            ..self.projection
+        })
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    // height: world units visible vertically, > 0, and 0 < near < far. Nothing changes
+    // otherwise
+    pub fn set_orthographic(&mut self, height: f64, near: f64, far: f64) -> Result<(), JsValue> {
+        self.set_projection(Projection {
+            kind: ProjectionKind::Orthographic,
+            height,
+            near,
+            far,
+            ..self.projection
+        })
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    // resizes the target (the canvas) and everything that depends on its size.
+    // Nothing changes when the framebuffer cannot get that large
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), JsValue> {
+        self.framebuffer
+            .resize(width, height)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.target.resize(width, height);
+        self.update_projection();
+        Ok(())
+    }
+
+    // render into part of the target only, in fractions of its size: (0, 0, 1, 1) is all
+    // of it, so several scenes can share a canvas for split screen or picture in picture
+    pub fn set_viewport(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.viewport = Viewport::new(x, y, width, height);
+        self.update_projection();
+    }
+
+    // further limit drawing inside the viewport, in fractions of the target size
+    pub fn set_scissor(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.scissor = Some(Viewport::new(x, y, width, height));
+    }
+
+    pub fn clear_scissor(&mut self) {
+        self.scissor = None;
+    }
+
+    // how far past the viewport edges triangles are kept whole, as a multiple of its
+    // size: 1 clips exactly at the edges
+    pub fn set_guard_band(&mut self, factor: f64) {
+        self.guard_band = factor.max(1.0);
+    }
+
+    // light coming from every direction, linear RGB
+    pub fn set_ambient(&mut self, r: f64, g: f64, b: f64) {
+        self.lighting.ambient = [r, g, b];
+    }
+
+    // new white light of intensity 1, returns its id for the set_light_* methods
+    pub fn add_light(&mut self, kind: LightKind) -> u32 {
+        self.lighting.add(Light::new(kind)) as u32
+    }
+
+    pub fn remove_light(&mut self, light: u32) {
+        self.lighting.remove(light as usize);
+    }
+
+    // removes every light, including the default one
+    pub fn clear_lights(&mut self) {
+        self.lighting.clear();
+    }
+
+    pub fn set_light_enabled(&mut self, light: u32, enabled: bool) {
+        if let Some(light) = self.lighting.get_mut(light as usize) {
+            light.enabled = enabled;
+        }
+    }
+
+    // linear RGB, scaled by the intensity
+    pub fn set_light_color(&mut self, light: u32, r: f64, g: f64, b: f64, intensity: f64) {
+        if let Some(light) = self.lighting.get_mut(light as usize) {
+            light.color = [r, g, b];
+            light.intensity = intensity;
+        }
+    }
+
+    // world position of point and spot lights
+    pub fn set_light_position(&mut self, light: u32, x: f64, y: f64, z: f64) {
+        if let Some(light) = self.lighting.get_mut(light as usize) {
+            light.position = Vec3D::new(x, y, z);
+        }
+    }
+
+    // direction the light travels in, directional and spot lights
+    pub fn set_light_direction(&mut self, light: u32, x: f64, y: f64, z: f64) {
+        if let Some(light) = self.lighting.get_mut(light as usize) {
+            light.direction = Vec3D::new(x, y, z);
+        }
+    }
+
+    // falloff 1 / (constant + linear * d + quadratic * d²) with the distance d
+    pub fn set_light_attenuation(
+        &mut self,
+        light: u32,
+        constant: f64,
+        linear: f64,
+        quadratic: f64,
+    ) {
+        if let Some(light) = self.lighting.get_mut(light as usize) {
+            light.attenuation = [constant, linear, quadratic];
+        }
+    }
+
+    // spot cone half angles in radians, the light fades out between inner and outer
+    pub fn set_light_cone(&mut self, light: u32, inner: f64, outer: f64) {
+        if let Some(light) = self.lighting.get_mut(light as usize) {
+            light.inner_angle = inner;
+            light.outer_angle = outer;
+        }
+    }
+
+    // flat, Gouraud or Phong
+    pub fn set_shading(&mut self, shading: ShadingMode) {
+        self.shading = shading;
+    }
+
+    // filled with or without outline, wireframe, hidden line or points
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.raster.mode = mode;
+    }
+
+    // color of the edges and points, linear RGB and opacity in [0, 1]
+    pub fn set_line_color(&mut self, r: f64, g: f64, b: f64, opacity: f64) {
+        self.line_style.color = Scene::rgb_to_color([r, g, b], opacity);
+    }
+
+    // in pixels
+    pub fn set_line_width(&mut self, width: f64) {
+        self.line_style.width = width.max(0.0);
+    }
+
+    // smooth (Xiaolin Wu) or aliased (Bresenham) edges
+    pub fn set_line_antialiasing(&mut self, enabled: bool) {
+        self.line_style.antialiased = enabled;
+    }
+
+    // side of the triangles left out, CullMode::None shows models with holes from
+    // inconsistent winding
+    pub fn set_cull_mode(&mut self, cull: CullMode) {
+        self.raster.cull = cull;
+    }
+
+    // corner order of front faces on screen, clockwise by default
+    pub fn set_front_face(&mut self, winding: Winding) {
+        self.raster.front_face = winding;
+    }
+
+    // lights the side of the triangles that is seen, whatever their winding
+    pub fn set_two_sided_lighting(&mut self, enabled: bool) {
+        self.raster.two_sided = enabled;
+    }
+
+    // single keypress, moves the camera a short step
+    pub fn input(&mut self, key: &str) {
+        self.input.key_press(key);
+    }
+
+    pub fn key_down(&mut self, key: &str) {
+        self.input.key_down(key);
+    }
+
+    pub fn key_up(&mut self, key: &str) {
+        self.input.key_up(key);
+    }
+
+    pub fn mouse_down(&mut self) {
+        self.input.mouse_down();
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.input.mouse_up();
+    }
+
+    // pixel deltas, like MouseEvent movementX / movementY
+    pub fn mouse_move(&mut self, dx: f64, dy: f64) {
+        self.input.mouse_move(dx, dy);
+    }
+
+    pub fn wheel(&mut self, delta: f64) {
+        self.input.wheel(delta);
+    }
+
+    // world units per second for WASD, radians per pixel for mouse drag
+    pub fn set_input_speed(&mut self, move_speed: f64, look_speed: f64) {
+        self.input.move_speed = move_speed;
+        self.input.look_speed = look_speed;
+    }
+
+    // time in seconds, drives frame time independent camera motion
     pub fn tick(&mut self, time: f64) {
-        self.ctx.clear_rect(
-            0.into(),
-            0.into(),
-            self.canvas.width().into(),
-            self.canvas.height().into(),
-        );
+        self.animation.advance(time);
+        self.animation.apply(&mut self.graph);
+        self.input.update(&mut self.camera, time);
+        self.render();
+    }
+
+    // RGBA pixels of a frame rendered at another size than the target, rows from top to
+    // bottom. The camera does not move and the target is left untouched.
+    pub fn render_to_image(&mut self, width: u32, height: u32) -> Result<Vec<u8>, JsValue> {
+        self.render_image(width, height)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    pub fn render_to_png(&mut self, width: u32, height: u32) -> Result<Vec<u8>, JsValue> {
+        let pixels = self.render_to_image(width, height)?;
+        image::encode_png(width, height, &pixels).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    // binary ppm, without alpha
+    pub fn render_to_ppm(&mut self, width: u32, height: u32) -> Result<Vec<u8>, JsValue> {
+        let pixels = self.render_to_image(width, height)?;
+        image::encode_ppm(width, height, &pixels).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    // draws a frame with the current camera and presents it to the target. Skipped
+    // when the target grew larger than a framebuffer can be
+    fn render(&mut self) {
+        let (width, height) = self.target.size();
+        if (width, height) != (self.framebuffer.width(), self.framebuffer.height()) {
+            if self.framebuffer.resize(width, height).is_err() {
+                return;
+            }
+            self.update_projection();
+        }
+        let viewport = self.viewport.to_rect(width, height);
+        let scissor = match self.scissor {
+            Some(scissor) => scissor.to_rect(width, height).intersect(&viewport),
+            None => viewport,
+        };
+        self.framebuffer.set_scissor(Some(scissor));
+        self.framebuffer.clear(TRANSPARENT);
+        self.framebuffer.clear_depth();
 
-        let mut tris: Vec<Tri3D> = self.tris.clone();
-        Scene::apply_transforms(&mut tris, time);
-        Scene::keep_visible(&mut tris, &self.camera.pos, Vec3D::new(0.0, 1.0, -1.0));
+        self.camera.compute_view();
+        let view_projection = *self.camera.view_matrix() * self.projection_matrix;
+        let frustum = Frustum::from_matrix(&view_projection);
+        let viewer = match self.projection.kind {
+            ProjectionKind::Perspective => Viewer::Eye(self.camera.pos),
+            ProjectionKind::Orthographic => Viewer::Direction(self.camera.look_dir),
+        };
+        let raster = self.raster;
 
-        Scene::to_view(&mut tris, &mut self.camera);
-        // clip near plane
-        Scene::clip_tris(
+        // objects out of the frustum are skipped whole, the shared vertices of the
+        // others are taken to world and clip space once, then triangles, edges or points
+        // are made from them
+        let mut tris = Vec::new();
+        let mut edges = Vec::new();
+        let mut points = Vec::new();
+        let mut materials = Vec::new();
+        self.graph
+            .for_each_mesh(|mesh, transforms, material, groups, bounds| {
+                let world = Scene::world_matrix(transforms);
+                match bounds {
+                    Some(bounds) if frustum.intersects(&bounds.transformed(&world)) => {}
+                    _ => return,
+                }
+                let mut vertices: Vec<Vec3D> = mesh.vertices.iter().map(|v| v.pos).collect();
+                let mut normals: Vec<Vec3D> = mesh.vertices.iter().map(|v| v.attr.normal).collect();
+                Scene::apply_transforms(&mut vertices, &mut normals, &world);
+                match raster.mode {
+                    RenderMode::Wireframe => {
+                        Scene::mesh_edges(mesh, &vertices, |_| true, &mut edges)
+                    }
+                    RenderMode::Outlined | RenderMode::HiddenLine => Scene::mesh_edges(
+                        mesh,
+                        &vertices,
+                        |tri| !raster.is_culled(tri, &viewer),
+                        &mut edges,
+                    ),
+                    RenderMode::Points => points.extend_from_slice(&vertices),
+                    RenderMode::Solid => {}
+                }
+                if raster.mode.fills() {
+                    materials.push(*material);
+                    let clip: Vec<Vec3D> =
+                        vertices.iter().map(|v| v.mul(&view_projection)).collect();
+                    let mut mesh_tris =
+                        Scene::assemble(mesh, &vertices, &clip, &normals, materials.len() - 1);
+                    for (group, material) in mesh.groups.iter().zip(groups) {
+                        if let Some(material) = material {
+                            materials.push(*material);
+                            let end = (group.start + group.len).min(mesh_tris.len());
+                            for tri in mesh_tris[group.start.min(end)..end].iter_mut() {
+                                tri.material = materials.len() - 1;
+                            }
+                        }
+                    }
+                    tris.extend(mesh_tris);
+                }
+            });
+        Scene::cull_faces(&mut tris, &viewer, &self.raster);
+        Scene::light_tris(
             &mut tris,
-            Vec3D::new(0.0, 0.0, 0.1),
-            Vec3D::new(0.0, 0.0, 1.0),
+            &self.camera.pos,
+            &viewer,
+            &self.lighting,
+            &materials,
+            self.shading,
+            &self.raster,
         );
-        Scene::project(&mut tris, &self.projection_matrix);
+
+        Scene::clip_tris(&mut tris, self.guard_band);
         Scene::to_ndc(&mut tris);
-        Scene::ndc_to_screen(&mut tris, self.canvas.width(), self.canvas.height());
-        // sort via z coordinate
-        tris.sort();
+        Scene::ndc_to_screen(&mut tris, &viewport);
+        let edges = Scene::edges_to_screen(&edges, &view_projection, self.guard_band, &viewport);
+        let points = Scene::points_to_screen(&points, &view_projection, &viewport);
+        // the rasterizer stays within the scissor, which is inside the viewport
+        match self.raster.mode {
+            RenderMode::Outlined => {
+                Scene::draw_from_vec(
+                    &tris,
+                    &mut self.framebuffer,
+                    self.shading,
+                    &self.lighting,
+                    &materials,
+                    &self.graph,
+                    &self.camera.pos,
+                    Some(&self.line_style),
+                );
+                Scene::draw_edges(&edges, &mut self.framebuffer, &self.line_style, true);
+            }
+            RenderMode::Solid => Scene::draw_from_vec(
+                &tris,
+                &mut self.framebuffer,
+                self.shading,
+                &self.lighting,
+                &materials,
+                &self.graph,
+                &self.camera.pos,
+                None,
+            ),
+            RenderMode::Wireframe => {
+                Scene::draw_edges(&edges, &mut self.framebuffer, &self.line_style, false)
+            }
+            RenderMode::HiddenLine => {
+                Scene::draw_hidden_line(&tris, &edges, &mut self.framebuffer, &self.line_style)
+            }
+            RenderMode::Points => {
+                Scene::draw_points(&points, &mut self.framebuffer, &self.line_style)
+            }
+        }
+        self.target.present(&self.framebuffer, scissor);
+    }
 
-        Scene::clip_tris(
-            &mut tris,
-            Vec3D::new(0.0, 0.0, 0.0),
-            Vec3D::new(0.0, 1.0, 0.0),
-        );
-        Scene::clip_tris(
-            &mut tris,
-            Vec3D::new(0.0, (self.canvas.height() - 1) as f64, 0.0),
-            Vec3D::new(0.0, -1.0, 0.0),
-        );
-        Scene::clip_tris(
-            &mut tris,
-            Vec3D::new(0.0, 0.0, 0.0),
-            Vec3D::new(1.0, 0.0, 0.0),
-        );
-        Scene::clip_tris(
-            &mut tris,
-            Vec3D::new((self.canvas.width() - 1) as f64, 0.0, 0.0),
-            Vec3D::new(-1.0, 0.0, 0.0),
-        );
-        Scene::draw_from_vec(&tris, &mut self.ctx);
+    // linear RGB and opacity to framebuffer color, overexposed channels saturate
+    fn rgb_to_color(rgb: [f64; 3], opacity: f64) -> Color {
+        let [r, g, b] = rgb.map(|channel| (channel.clamp(0.0, 1.0) * 255.0) as u8);
+        [r, g, b, (opacity.clamp(0.0, 1.0) * 255.0) as u8]
     }
 
-    fn draw_hollow(tri: &Tri3D, ctx: &mut web_sys::CanvasRenderingContext2d) {
-        ctx.begin_path();
-        ctx.move_to(tri[0][0], tri[0][1]);
-        ctx.line_to(tri[1][0], tri[1][1]);
-        ctx.line_to(tri[2][0], tri[2][1]);
-        ctx.close_path();
-        ctx.stroke();
+    // With an outline style the surfaces are pushed back like for the hidden lines, so
+    // the edges drawn over them afterwards are not hidden by their own triangles.
+    // Opaque triangles go first, then the ones that may not be (translucent material or
+    // texture) from back to front, each blending over what is behind it.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_from_vec(
+        tris: &[Tri3D],
+        framebuffer: &mut Framebuffer,
+        shading: ShadingMode,
+        lighting: &Lighting,
+        materials: &[Material],
+        graph: &SceneGraph,
+        eye: &Vec3D,
+        outline: Option<&LineStyle>,
+    ) {
+        let translucent = |tri: &&Tri3D| {
+            let material = &materials[tri.material];
+            material.opacity < 1.0 || material.texture.is_some()
+        };
+        let depth = |tri: &Tri3D| tri[0].z + tri[1].z + tri[2].z;
+        let mut ordered: Vec<&Tri3D> = tris.iter().filter(|tri| !translucent(tri)).collect();
+        let mut back_to_front: Vec<&Tri3D> = tris.iter().filter(translucent).collect();
+        back_to_front.sort_by(|a, b| depth(b).total_cmp(&depth(a)));
+        ordered.extend(back_to_front);
+
+        for tri in ordered {
+            let pushed = outline.map(|style| Scene::pushed_back(tri, style));
+            let tri = pushed.as_ref().unwrap_or(tri);
+            let material = &materials[tri.material];
+            let texture = material.texture.and_then(|id| graph.texture(id));
+            rasterizer::fill_tri(framebuffer, tri, |fragment| {
+                let attr = VertexAttr::barycentric(&tri.attrs, &fragment.perspective);
+                // the texture tints the diffuse color, fully transparent texels are holes
+                let texel = texture.map_or([1.0; 4], |texture| {
+                    let uvs = tri.attrs.map(|attr| attr.uv);
+                    let derivative = |weights: [f64; 3]| {
+                        uvs[0].scale(weights[0])
+                            + uvs[1].scale(weights[1])
+                            + uvs[2].scale(weights[2])
+                    };
+                    texture.sample_grad(
+                        &attr.uv,
+                        &derivative(fragment.dx),
+                        &derivative(fragment.dy),
+                    )
+                });
+                if texel[3] == 0.0 {
+                    return None;
+                }
+                let opacity = material.opacity * texel[3];
+                let tint =
+                    |rgb: [f64; 3]| [rgb[0] * texel[0], rgb[1] * texel[1], rgb[2] * texel[2]];
+                match shading {
+                    ShadingMode::Flat => Some(Self::rgb_to_color(tint(tri.color), opacity)),
+                    ShadingMode::Gouraud => Some(Self::rgb_to_color(tint(attr.color), opacity)),
+                    ShadingMode::Phong => {
+                        let textured = Material {
+                            diffuse: tint(material.diffuse),
+                            ..*material
+                        };
+                        let normal = attr.normal.normalized();
+                        let rgb = lighting.shade(&attr.world, &normal, eye, &textured);
+                        Some(Self::rgb_to_color(rgb, opacity))
+                    }
+                }
+            });
+        }
     }
 
-    fn luminance_to_rgb(luminance: f64) -> JsValue {
-        format!(
-            "rgb({},{},{})",
-            (luminance * 255.0) as u8,
-            (luminance * 255.0) as u8,
-            (luminance * 255.0) as u8,
-        )
-        .into()
+    fn draw_edges(
+        edges: &[[Vec3D; 2]],
+        framebuffer: &mut Framebuffer,
+        style: &LineStyle,
+        depth_test: bool,
+    ) {
+        for [a, b] in edges {
+            rasterizer::draw_line(framebuffer, a, b, style, depth_test);
+        }
     }
 
-    fn draw_tri(tri: &Tri3D, ctx: &mut web_sys::CanvasRenderingContext2d) {
-        ctx.begin_path();
-        ctx.move_to(tri[0][0], tri[0][1]);
-        ctx.line_to(tri[1][0], tri[1][1]);
-        ctx.line_to(tri[2][0], tri[2][1]);
-        ctx.set_fill_style(&Self::luminance_to_rgb(tri.l));
-        ctx.fill();
+    // The depth of the triangle grown by its depth change over the line half width, so
+    // the pixels of its own edges that fall next to the exact line are not hidden
+    fn pushed_back(tri: &Tri3D, style: &LineStyle) -> Tri3D {
+        let offset = rasterizer::depth_slope(tri) * (style.width * 0.5 + 1.0);
+        let mut pushed = *tri;
+        for i in 0..3 {
+            pushed[i].z += offset;
+        }
+        pushed
     }
 
-    fn draw_from_vec(tris: &Vec<Tri3D>, ctx: &mut web_sys::CanvasRenderingContext2d) {
+    // The surfaces only fill the depth buffer, pushed back, which then hides the edges
+    // behind them
+    fn draw_hidden_line(
+        tris: &[Tri3D],
+        edges: &[[Vec3D; 2]],
+        framebuffer: &mut Framebuffer,
+        style: &LineStyle,
+    ) {
         for tri in tris {
-            Scene::draw_tri(tri, ctx);
-            Scene::draw_hollow(tri, ctx);
+            rasterizer::fill_depth(framebuffer, &Scene::pushed_back(tri, style));
         }
+        Scene::draw_edges(edges, framebuffer, style, true);
     }
 
-    fn apply_transforms(tris: &mut Vec<Tri3D>, time: f64) {
-        let rotate_x = &Mat3D::rot_x(time * 1.3);
-        let rotate_y = &Mat3D::rot_y(time);
-        let translation_vec = Vec3D::new(0.0, 0.0, 20000.0);
-
-        for tri in tris {
-            tri[0] = tri[0].mul(rotate_x);
-            tri[1] = tri[1].mul(rotate_x);
-            tri[2] = tri[2].mul(rotate_x);
+    // a small square per vertex in the line color, depth tested against the other points
+    fn draw_points(points: &[Vec3D], framebuffer: &mut Framebuffer, style: &LineStyle) {
+        let scissor = framebuffer.scissor();
+        for point in points {
+            let x0 = (point.x - POINT_SIZE as f64 * 0.5).round() as i64;
+            let y0 = (point.y - POINT_SIZE as f64 * 0.5).round() as i64;
+            for y in y0..y0 + POINT_SIZE {
+                for x in x0..x0 + POINT_SIZE {
+                    if !scissor.contains(x, y) || point.z >= framebuffer.depth(x as u32, y as u32) {
+                        continue;
+                    }
+                    framebuffer.set_depth(x as u32, y as u32, point.z);
+                    framebuffer.blend_pixel(x, y, style.color);
+                }
+            }
+        }
+    }
 
-            tri[0] = tri[0].mul(rotate_y);
-            tri[1] = tri[1].mul(rotate_y);
-            tri[2] = tri[2].mul(rotate_y);
+    // node transform first, then its parents up to the root, composed into a single
+    // matrix
+    fn world_matrix(transforms: &[Transform]) -> Mat3D {
+        transforms
+            .iter()
+            .fold(Mat3D::identity(), |m, transform| m * transform.matrix())
+    }
 
-            tri[0] = tri[0] + translation_vec;
-            tri[1] = tri[1] + translation_vec;
-            tri[2] = tri[2] + translation_vec;
+    // Normals take the normal matrix so they stay perpendicular to the surface when it
+    // is stretched.
+    fn apply_transforms(vertices: &mut [Vec3D], normals: &mut [Vec3D], world: &Mat3D) {
+        for vertex in vertices.iter_mut() {
+            *vertex = vertex.mul(world);
+        }
+        // a zero scale flattens the mesh, its normals are left as they are
+        let normal_matrix = world.normal_matrix();
+        for normal in normals.iter_mut() {
+            if let Some(matrix) = &normal_matrix {
+                *normal = normal.mul(matrix);
+            }
+            if normal.length() > 0.0 {
+                *normal = normal.normalized();
+            }
         }
     }
 
-    // for some reasons, compiler crashes here if i don't obfuscate my code with arrays
-    fn keep_visible(tris: &mut Vec<Tri3D>, camera_pos: &Vec3D, mut light_direction: Vec3D) {
-        let mut res = Vec::new();
-        light_direction = light_direction.normalized();
+    // triangles of the mesh in clip space, world positions and normals in their attrs,
+    // all taken from the already transformed vertices
+    fn assemble(
+        mesh: &Mesh,
+        world: &[Vec3D],
+        clip: &[Vec3D],
+        normals: &[Vec3D],
+        material: usize,
+    ) -> Vec<Tri3D> {
+        let attr = |i: usize| VertexAttr {
+            normal: normals[i],
+            world: world[i],
+            ..mesh.vertices[i].attr
+        };
+        mesh.indices
+            .iter()
+            .map(|face| {
+                let [a, b, c] = face.map(|i| i as usize);
+                Tri3D {
+                    material,
+                    ..Tri3D::new_with_attrs(clip[a], clip[b], clip[c], [attr(a), attr(b), attr(c)])
+                }
+            })
+            .collect()
+    }
 
-        for tri in tris.iter() {
-            let t = [
-                tri[0].x, tri[0].y, tri[0].z, tri[1].x, tri[1].y, tri[1].z, tri[2].x, tri[2].y,
-                tri[2].z,
-            ];
-            let line1 = [t[3] - t[0], t[4] - t[1], t[5] - t[2]];
-            let line2 = [t[6] - t[0], t[7] - t[1], t[8] - t[2]];
-            let normal = Vec3D::new(line1[0], line1[1], line1[2])
-                .cross_product(Vec3D::new(line2[0], line2[1], line2[2]))
-                .normalized();
-            // are equivalent to :
-            // let line1 = tri[1] - tri[0];
-            // let line2 = tri[3] - tri[0];
-            // let normal = line1.cross_product(line2).normalized();
-            let camera_ray = tri[0] - *camera_pos;
-            if normal.dot_product(camera_ray) < 0.0 {
-                res.push(Tri3D::new(
-                    Vec3D::new(t[0], t[1], t[2]),
-                    Vec3D::new(t[3], t[4], t[5]),
-                    Vec3D::new(t[6], t[7], t[8]),
-                ));
-
-                // compute luminance
-                let len = res.len();
-                res[len - 1].l = 0.1_f64.max(light_direction.dot_product(normal));
+    // Every edge of the triangles kept by `keep` once, as world space segments. Corners
+    // are matched by their model position, so edges between vertices split for their
+    // normals or uvs are not drawn twice either
+    fn mesh_edges<F>(mesh: &Mesh, vertices: &[Vec3D], keep: F, edges: &mut Vec<[Vec3D; 2]>)
+    where
+        F: Fn(&Tri3D) -> bool,
+    {
+        let key = |i: usize| {
+            let p = mesh.vertices[i].pos;
+            [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+        };
+        let mut seen = HashSet::new();
+        for face in mesh.indices.iter() {
+            let [a, b, c] = face.map(|i| i as usize);
+            if !keep(&Tri3D::new(vertices[a], vertices[b], vertices[c])) {
+                continue;
+            }
+            for (i, j) in [(a, b), (b, c), (c, a)] {
+                let (ki, kj) = (key(i), key(j));
+                if ki != kj && seen.insert(if ki < kj { (ki, kj) } else { (kj, ki) }) {
+                    edges.push([vertices[i], vertices[j]]);
+                }
             }
         }
+    }
 
-        *tris = res;
+    // world space segments clipped to the frustum and taken to the viewport
+    fn edges_to_screen(
+        edges: &[[Vec3D; 2]],
+        view_projection: &Mat3D,
+        guard_band: f64,
+        viewport: &Rect,
+    ) -> Vec<[Vec3D; 2]> {
+        edges
+            .iter()
+            .filter_map(|[a, b]| {
+                let (a, b) = (a.mul(view_projection), b.mul(view_projection));
+                let [a, b] = clip::clip_segment(&a, &b, guard_band)?;
+                Some([
+                    Scene::clip_to_screen(a, viewport),
+                    Scene::clip_to_screen(b, viewport),
+                ])
+            })
+            .collect()
     }
 
-    fn to_view(tris: &mut Vec<Tri3D>, camera: &mut Camera) {
-        camera.compute_view();
+    // world space points inside the frustum, taken to the viewport
+    fn points_to_screen(points: &[Vec3D], view_projection: &Mat3D, viewport: &Rect) -> Vec<Vec3D> {
+        points
+            .iter()
+            .map(|p| p.mul(view_projection))
+            .filter(clip::contains)
+            .map(|p| Scene::clip_to_screen(p, viewport))
+            .collect()
+    }
 
-        for i in 0..tris.len() {
-            tris[i] = camera.to_view(tris[i]);
-        }
+    // world space corners of a clip space triangle from assemble
+    fn world_tri(tri: &Tri3D) -> Tri3D {
+        Tri3D::new(tri.attrs[0].world, tri.attrs[1].world, tri.attrs[2].world)
     }
 
-    fn clip_tris(tris: &mut Vec<Tri3D>, plane_pos: Vec3D, plane_normal: Vec3D) {
-        let mut res = Vec::new();
-        let plane_n = plane_normal.normalized();
-        for tri in tris.iter() {
-            let (nb_tris, tri1, tri2) = Tri3D::clip(&tri, &plane_pos, &plane_n);
-            match nb_tris {
-                0 => continue,
-                1 => res.push(tri1.unwrap()),
-                2 => {
-                    res.push(tri1.unwrap());
-                    res.push(tri2.unwrap())
+    // drops the triangles whose visible side is culled
+    fn cull_faces(tris: &mut Vec<Tri3D>, viewer: &Viewer, raster: &RasterState) {
+        tris.retain(|tri| !raster.is_culled(&Scene::world_tri(tri), viewer));
+    }
+
+    // Lights the triangles per triangle or per corner depending on the shading mode
+    // (Phong shading lights pixels later on). Corners without a normal take the face one.
+    fn light_tris(
+        tris: &mut [Tri3D],
+        camera_pos: &Vec3D,
+        viewer: &Viewer,
+        lighting: &Lighting,
+        materials: &[Material],
+        shading: ShadingMode,
+        raster: &RasterState,
+    ) {
+        for tri in tris.iter_mut() {
+            let world = Scene::world_tri(tri);
+            let mut normal = raster.front_normal(&world);
+            if raster.two_sided {
+                // the side seen is lit, whatever the winding says: the face normal
+                // turns to the camera and the corner normals follow it
+                if normal.dot_product(viewer.ray_to(&world[0])) > 0.0 {
+                    normal = normal.scale(-1.0);
                 }
-                _ => panic!("nb of tris illegal: {}", nb_tris),
+                for attr in tri.attrs.iter_mut() {
+                    if attr.normal.dot_product(normal) < 0.0 {
+                        attr.normal = attr.normal.scale(-1.0);
+                    }
+                }
+            }
+            for attr in tri.attrs.iter_mut() {
+                if attr.normal.length() == 0.0 {
+                    attr.normal = normal;
+                }
+            }
+
+            let material = &materials[tri.material];
+            match shading {
+                // light received at the center of the triangle
+                ShadingMode::Flat => {
+                    let center = (world[0] + world[1] + world[2]).scale(1.0 / 3.0);
+                    tri.color = lighting.shade(&center, &normal, camera_pos, material);
+                }
+                ShadingMode::Gouraud => {
+                    for attr in tri.attrs.iter_mut() {
+                        attr.color =
+                            lighting.shade(&attr.world, &attr.normal, camera_pos, material);
+                    }
+                }
+                ShadingMode::Phong => {}
             }
         }
+    }
+
+    // against the six frustum planes in clip space, before the divide by w
+    fn clip_tris(tris: &mut Vec<Tri3D>, guard_band: f64) {
+        let mut res = Vec::with_capacity(tris.len());
+        for tri in tris.iter() {
+            clip::clip_tri(tri, guard_band, &mut res);
+        }
 
         *tris = res;
     }
 
-    fn project(tris: &mut Vec<Tri3D>, projection_matrix: &Mat3D) {
-        for i in 0..tris.len() {
-            tris[i] = tris[i].mul(projection_matrix);
+    fn to_ndc(tris: &mut [Tri3D]) {
+        for tri in tris {
+            for i in 0..3 {
+                Scene::point_to_ndc(&mut tri[i]);
+            }
         }
     }
 
-    fn to_ndc(tris: &mut Vec<Tri3D>) {
+    // perspective divide, y flipped to point down like the screen rows.
+    fn point_to_ndc(vertex: &mut Vec3D) {
+        vertex.x /= vertex.w;
+        vertex.y = -(vertex.y / vertex.w);
+        vertex.z /= vertex.w;
+    }
+
+    // single clip space point to the viewport, for the edges and points
+    fn clip_to_screen(mut p: Vec3D, viewport: &Rect) -> Vec3D {
+        Scene::point_to_ndc(&mut p);
+        Scene::point_to_screen(&mut p, viewport);
+        p
+    }
+
+    fn ndc_to_screen(tris: &mut [Tri3D], viewport: &Rect) {
         for tri in tris {
-            for vertex in [tri[0], tri[1], tri[2]].iter_mut() {
-                vertex.x = -1.0 * (vertex.x / vertex.w);
-                vertex.y = -1.0 * (vertex.y / vertex.w);
-                vertex.z = vertex.z / vertex.w;
+            for i in 0..3 {
+                Scene::point_to_screen(&mut tri[i], viewport);
             }
         }
     }
 
-    // take NDC coordinates [(-1,-1), (1,1)] to screen [(0, 0), (width, height)]
-    // x = w(x + 1) / 2
-    // y = h(y + 1) / 2
+    // take NDC coordinates [(-1,-1), (1,1)] to the viewport [(x, y), (x + width, y + height)]
+    // x = x0 + w(x + 1) / 2
+    // y = y0 + h(y + 1) / 2
     // z = z
-    fn ndc_to_screen(tris: &mut Vec<Tri3D>, width: u32, height: u32) {
-        let w = width as f64;
-        let h = height as f64;
-        for tri in tris {
-            tri[0].x = w * (tri[0].x + 1.0) * 0.5;
-            tri[0].y = h * (tri[0].y + 1.0) * 0.5;
+    fn point_to_screen(p: &mut Vec3D, viewport: &Rect) {
+        let (x0, y0) = (viewport.x as f64, viewport.y as f64);
+        p.x = x0 + viewport.width as f64 * (p.x + 1.0) * 0.5;
+        p.y = y0 + viewport.height as f64 * (p.y + 1.0) * 0.5;
+    }
+}
+
+// native side: same pipeline, no browser needed
+impl Scene {
+    fn update_projection(&mut self) {
+        let viewport = self
+            .viewport
+            .to_rect(self.framebuffer.width(), self.framebuffer.height());
+        self.projection
+            .set_screen_size(viewport.width, viewport.height);
+        self.projection_matrix = self.projection.matrix();
+    }
+
+    pub fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    // the aspect ratio follows the viewport whatever `projection` says
+    pub fn set_projection(&mut self, projection: Projection) -> Result<(), ProjectionError> {
+        projection.check()?;
+        self.projection = projection;
+        self.update_projection();
+        Ok(())
+    }
+
+    // scene with `mesh` as node 0, placed in front of the camera
+    pub fn with_target(
+        target: Box<dyn RenderTarget>,
+        mesh: Mesh,
+    ) -> Result<Scene, FramebufferError> {
+        let mut graph = SceneGraph::new();
+        let mesh = graph.add_mesh(mesh);
+        let node = graph.add_node(None, Some(mesh));
+        if let Some(node) = graph.node_mut(node) {
+            node.transform.translation = Vec3D::new(0.0, 0.0, 2.0);
+        }
+
+        let (width, height) = target.size();
+        let mut projection = Projection::default();
+        projection.set_screen_size(width, height);
+        Ok(Scene {
+            framebuffer: Framebuffer::new(width, height)?,
+            target,
+            graph,
+            animation: Timeline::new(),
+            camera: Camera::new(),
+            input: Input::new(),
+            lighting: Lighting::default(),
+            shading: ShadingMode::Flat,
+            raster: RasterState::default(),
+            line_style: LineStyle::default(),
+            projection_matrix: projection.matrix(),
+            viewport: Viewport::default(),
+            scissor: None,
+            guard_band: GUARD_BAND,
+            projection,
+        })
+    }
+
+    pub fn headless(width: u32, height: u32, mesh: Mesh) -> Result<Scene, FramebufferError> {
+        Scene::with_target(Box::new(Headless::new(width, height)), mesh)
+    }
+
+    // Adds the materials of a library to the graph and gives each group of the mesh
+    // the one its usemtl names, groups naming none of them keep the mesh material.
+    // Returns the ids of the materials in library order.
+    pub fn link_materials(&mut self, mesh: usize, library: &[obj::MtlMaterial]) -> Vec<usize> {
+        let ids: Vec<usize> = library
+            .iter()
+            .map(|mtl| self.graph.add_material(Material::from(mtl)))
+            .collect();
+        let names: Vec<Option<String>> = match self.graph.mesh(mesh) {
+            Some(mesh) => mesh.groups.iter().map(|g| g.material.clone()).collect(),
+            None => return ids,
+        };
+        for (group, name) in names.iter().enumerate() {
+            let material = name
+                .as_ref()
+                .and_then(|name| library.iter().position(|mtl| &mtl.name == name))
+                .map(|i| ids[i]);
+            if material.is_some() {
+                self.graph.set_group_material(mesh, group, material);
+            }
+        }
+        ids
+    }
+
+    // render_to_image without JsValue errors, nothing changes when the framebuffer
+    // cannot take that size
+    pub fn render_image(&mut self, width: u32, height: u32) -> Result<Vec<u8>, FramebufferError> {
+        self.framebuffer.resize(width, height)?;
+        self.update_projection();
+        let target = std::mem::replace(&mut self.target, Box::new(Headless::new(width, height)));
+        self.render();
+        let pixels = self.framebuffer.pixels().to_vec();
+
+        self.target = target;
+        let (width, height) = self.target.size();
+        // it fit before, and render() tries again otherwise
+        if self.framebuffer.resize(width, height).is_ok() {
+            self.update_projection();
+        }
+        Ok(pixels)
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn lighting(&self) -> &Lighting {
+        &self.lighting
+    }
+
+    pub fn lighting_mut(&mut self) -> &mut Lighting {
+        &mut self.lighting
+    }
+
+    pub fn graph(&self) -> &SceneGraph {
+        &self.graph
+    }
 
-            tri[1].x = w * (tri[1].x + 1.0) * 0.5;
-            tri[1].y = h * (tri[1].y + 1.0) * 0.5;
+    pub fn graph_mut(&mut self) -> &mut SceneGraph {
+        &mut self.graph
+    }
+
+    pub fn animation(&self) -> &Timeline {
+        &self.animation
+    }
+
+    pub fn animation_mut(&mut self) -> &mut Timeline {
+        &mut self.animation
+    }
+
+    // last rendered frame
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn cube() -> Mesh {
+        Mesh::from_facets(
+            &[
+                // south
+                Tri3D::from_points(0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0),
+                Tri3D::from_points(0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0),
+                // east
+                Tri3D::from_points(1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0),
+                Tri3D::from_points(1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0),
+                // north
+                Tri3D::from_points(1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0),
+                Tri3D::from_points(1.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0),
+                // west
+                Tri3D::from_points(0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0),
+                Tri3D::from_points(0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0),
+                // top
+                Tri3D::from_points(0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0),
+                Tri3D::from_points(0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0),
+                // bottom
+                Tri3D::from_points(1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0),
+                Tri3D::from_points(1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0),
+            ],
+            CREASE_ANGLE,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alpha(pixels: &[u8], width: u32, x: u32, y: u32) -> u8 {
+        pixels[((y * width + x) * 4 + 3) as usize]
+    }
 
-            tri[2].x = w * (tri[2].x + 1.0) * 0.5;
-            tri[2].y = h * (tri[2].y + 1.0) * 0.5;
+    #[test]
+    fn headless_render() {
+        let mut scene = Scene::headless(8, 8, Scene::cube()).unwrap();
+        let pixels = scene.render_image(32, 24).unwrap();
+        assert_eq!(pixels.len(), 32 * 24 * 4);
+        // the cube in front of the camera covers the center, not the corners
+        assert_eq!(alpha(&pixels, 32, 16, 12), 255);
+        assert_eq!(alpha(&pixels, 32, 0, 0), 0);
+        // the target keeps its size
+        assert_eq!(scene.framebuffer.width(), 8);
+
+        assert_eq!(
+            scene.render_image(100_000, 1),
+            Err(FramebufferError::Size {
+                width: 100_000,
+                height: 1,
+            })
+        );
+    }
+    #[test]
+    fn frame_node() {
+        let mut scene = Scene::headless(32, 24, Scene::cube()).unwrap();
+        scene.set_render_mode(RenderMode::Solid);
+        scene.set_scale(0, 100.0, 100.0, 100.0);
+        scene.set_translation(0, 500.0, 0.0, 2.0);
+        assert_eq!(alpha(&scene.render_image(32, 24).unwrap(), 32, 16, 12), 0);
+
+        assert!(scene.frame_node(0));
+        let pixels = scene.render_image(32, 24).unwrap();
+        // the whole cube is in view, around the center
+        assert_eq!(alpha(&pixels, 32, 16, 12), 255);
+        for (x, y) in [(0, 0), (31, 0), (0, 23), (31, 23)] {
+            assert_eq!(alpha(&pixels, 32, x, y), 0);
         }
+
+        let empty = scene.add_node(None, None);
+        assert!(!scene.frame_node(empty));
+    }
+
+    #[test]
+    fn group_materials() {
+        let quad = obj::load_obj(
+            b"mtllib quad.mtl
+v -1 -1 0
+v -1 1 0
+v 1 1 0
+v 1 -1 0
+usemtl red
+f 1 2 3 4
+",
+        )
+        .unwrap();
+        let library = obj::load_mtl(b"newmtl blue\nKd 0 0 1\nnewmtl red\nKd 1 0 0\n").unwrap();
+        let mut scene = Scene::headless(16, 16, quad).unwrap();
+        scene.set_cull_mode(CullMode::None);
+        scene.set_render_mode(RenderMode::Solid);
+
+        let ids = scene.link_materials(0, &library);
+        assert_eq!(ids.len(), 2);
+        let group = &scene.graph.mesh(0).unwrap().groups[0];
+        assert_eq!(group.material_id, Some(ids[1]));
+        let pixels = scene.render_image(16, 16).unwrap();
+        let center = &pixels[(8 * 16 + 8) * 4..][..4];
+        assert!(center[0] > 0 && center[1] == 0 && center[2] == 0);
+
+        // a node material replaces the group ones
+        scene.set_node_material(0, Some(ids[0] as u32));
+        let pixels = scene.render_image(16, 16).unwrap();
+        let center = &pixels[(8 * 16 + 8) * 4..][..4];
+        assert!(center[0] == 0 && center[2] > 0);
     }
 }
@@ -0,0 +1,91 @@
+use wasm_bindgen::{Clamped, JsCast};
+
+use crate::framebuffer::{Framebuffer, Rect};
+
+// Where a finished frame ends up. The pipeline only ever draws into a Framebuffer,
+// targets decide the output size and what to do with the pixels.
+pub trait RenderTarget {
+    fn size(&self) -> (u32, u32);
+    fn resize(&mut self, width: u32, height: u32);
+    // only `region` was rendered, pixels outside of it belong to someone else
+    fn present(&mut self, framebuffer: &Framebuffer, region: Rect);
+}
+
+// No display at all: the frame stays in the Scene framebuffer (tests, servers, CLI)
+pub struct Headless {
+    width: u32,
+    height: u32,
+}
+
+impl Headless {
+    pub fn new(width: u32, height: u32) -> Headless {
+        Headless { width, height }
+    }
+}
+
+impl RenderTarget for Headless {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    fn present(&mut self, _framebuffer: &Framebuffer, _region: Rect) {}
+}
+
+// Blits the framebuffer onto a 2d canvas
+pub struct Canvas {
+    canvas: web_sys::HtmlCanvasElement,
+    ctx: web_sys::CanvasRenderingContext2d,
+}
+
+impl Canvas {
+    pub fn new(canvas: web_sys::HtmlCanvasElement) -> Canvas {
+        let ctx = canvas
+            .get_context("2d")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<web_sys::CanvasRenderingContext2d>()
+            .unwrap();
+        ctx.set_image_smoothing_enabled(true);
+        Canvas { canvas, ctx }
+    }
+}
+
+impl RenderTarget for Canvas {
+    fn size(&self) -> (u32, u32) {
+        (self.canvas.width(), self.canvas.height())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.canvas.set_width(width);
+        self.canvas.set_height(height);
+    }
+
+    // several scenes can share a canvas, each one only writes its own region
+    fn present(&mut self, framebuffer: &Framebuffer, region: Rect) {
+        if region.width == 0 || region.height == 0 {
+            return;
+        }
+        let image = web_sys::ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(framebuffer.pixels()),
+            framebuffer.width(),
+            framebuffer.height(),
+        )
+        .unwrap();
+        self.ctx
+            .put_image_data_with_dirty_x_and_dirty_y_and_dirty_width_and_dirty_height(
+                &image,
+                0.0,
+                0.0,
+                region.x as f64,
+                region.y as f64,
+                region.width as f64,
+                region.height as f64,
+            )
+            .unwrap();
+    }
+}
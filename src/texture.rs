@@ -0,0 +1,585 @@
+use std::{error, fmt};
+
+use wasm_bindgen::prelude::*;
+
+use crate::vec2D::Vec2D;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    // closest texel, blocky when magnified
+    Nearest = 0,
+    // weighted average of the 4 closest texels
+    Bilinear = 1,
+    // bilinear in the two mip levels closest to the pixel footprint, blended
+    Trilinear = 2,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    // uv outside [0, 1] tile the image
+    Repeat = 0,
+    // uv outside [0, 1] stretch the border texels
+    Clamp = 1,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureError {
+    // the png decoder rejected the data
+    Png(String),
+    // malformed ppm header or truncated pixel data
+    Ppm(String),
+    // neither a png nor a ppm signature
+    UnknownFormat,
+    // no texel at all
+    Empty,
+    // the pixel buffer does not hold width * height RGBA texels
+    Size { expected: usize, found: usize },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Png(message) => write!(f, "png: {}", message),
+            TextureError::Ppm(message) => write!(f, "ppm: {}", message),
+            TextureError::UnknownFormat => write!(f, "texture: not a png or ppm image"),
+            TextureError::Empty => write!(f, "texture: empty image"),
+            TextureError::Size { expected, found } => write!(
+                f,
+                "texture: expected {} bytes of RGBA pixels, got {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl error::Error for TextureError {}
+
+// One image of a mip chain, 4 bytes per texel, rows from top to bottom
+#[derive(Debug, Clone)]
+struct Level {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Level {
+    // half the size (at least 1 texel), each texel averages the 2x2 texels it covers
+    fn downsample(&self) -> Level {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (x * 2 + dx).min(self.width - 1);
+                    let sy = (y * 2 + dy).min(self.height - 1);
+                    let i = (sy as usize * self.width as usize + sx as usize) * 4;
+                    for (channel, byte) in sum.iter_mut().zip(&self.pixels[i..i + 4]) {
+                        *channel += *byte as u32;
+                    }
+                }
+                pixels.extend(sum.iter().map(|channel| ((channel + 2) / 4) as u8));
+            }
+        }
+        Level {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+// RGBA image sampled with uv coordinates: (0, 0) is the bottom left corner, (1, 1) the
+// top right one, like OBJ texture coordinates.
+// Keeps a mip chain, each level half the size of the previous one down to 1x1, so
+// minified textures can be sampled without aliasing.
+#[derive(Debug, Clone)]
+pub struct Texture {
+    levels: Vec<Level>,
+    pub filter: Filter,
+    pub wrap: Wrap,
+    // most trilinear samples taken along a stretched pixel footprint, 1 disables the
+    // anisotropic filtering
+    pub anisotropy: u32,
+}
+
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G'];
+
+impl Texture {
+    // `pixels` is RGBA, rows from top to bottom
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Result<Texture, TextureError> {
+        if width == 0 || height == 0 {
+            return Err(TextureError::Empty);
+        }
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|texels| texels.checked_mul(4));
+        if expected != Some(pixels.len()) {
+            return Err(TextureError::Size {
+                expected: expected.unwrap_or(usize::MAX),
+                found: pixels.len(),
+            });
+        }
+        let mut levels = vec![Level {
+            width,
+            height,
+            pixels,
+        }];
+        while let Some(last) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+            levels.push(last.downsample());
+        }
+        Ok(Texture {
+            levels,
+            filter: Filter::Trilinear,
+            wrap: Wrap::Repeat,
+            anisotropy: 1,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.levels[0].height
+    }
+
+    // full size image
+    pub fn pixels(&self) -> &[u8] {
+        &self.levels[0].pixels
+    }
+
+    // number of mip levels, 1 for a 1x1 texture
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    // png or ppm, told apart by their signature
+    pub fn decode(bytes: &[u8]) -> Result<Texture, TextureError> {
+        if bytes.starts_with(PNG_SIGNATURE) {
+            Texture::from_png(bytes)
+        } else if bytes.starts_with(b"P3") || bytes.starts_with(b"P6") {
+            Texture::from_ppm(bytes)
+        } else {
+            Err(TextureError::UnknownFormat)
+        }
+    }
+
+    // any color type and bit depth, converted to 8 bit RGBA
+    pub fn from_png(bytes: &[u8]) -> Result<Texture, TextureError> {
+        let png_error = |e: png::DecodingError| TextureError::Png(e.to_string());
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(png_error)?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(png_error)?;
+
+        let channels = info.color_type.samples();
+        let mut pixels = Vec::with_capacity(info.width as usize * info.height as usize * 4);
+        for row in buf.chunks(info.line_size).take(info.height as usize) {
+            for texel in row[..info.width as usize * channels].chunks(channels) {
+                match texel {
+                    [gray] => pixels.extend_from_slice(&[*gray, *gray, *gray, 255]),
+                    [gray, alpha] => pixels.extend_from_slice(&[*gray, *gray, *gray, *alpha]),
+                    [r, g, b] => pixels.extend_from_slice(&[*r, *g, *b, 255]),
+                    _ => pixels.extend_from_slice(&texel[..4]),
+                }
+            }
+        }
+        Texture::new(info.width, info.height, pixels)
+    }
+
+    // P3 (ascii) or P6 (binary) portable pixmap, any max value
+    pub fn from_ppm(bytes: &[u8]) -> Result<Texture, TextureError> {
+        let mut pos = 0;
+        // whitespace separated header token, `#` comments run to the end of the line
+        let mut token = || -> Result<&[u8], TextureError> {
+            loop {
+                match bytes.get(pos) {
+                    Some(b'#') => {
+                        while bytes.get(pos).is_some_and(|&b| b != b'\n') {
+                            pos += 1;
+                        }
+                    }
+                    Some(b) if b.is_ascii_whitespace() => pos += 1,
+                    Some(_) => break,
+                    None => return Err(TextureError::Ppm("unexpected end of file".to_string())),
+                }
+            }
+            let start = pos;
+            while bytes.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+                pos += 1;
+            }
+            Ok(&bytes[start..pos])
+        };
+        let number = |token: &[u8]| -> Result<u32, TextureError> {
+            std::str::from_utf8(token)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| {
+                    TextureError::Ppm(format!(
+                        "invalid number {:?}",
+                        String::from_utf8_lossy(token)
+                    ))
+                })
+        };
+
+        let magic = token()?;
+        let binary = match magic {
+            b"P6" => true,
+            b"P3" => false,
+            _ => return Err(TextureError::Ppm("expected P3 or P6".to_string())),
+        };
+        let width = number(token()?)?;
+        let height = number(token()?)?;
+        let max = number(token()?)?;
+        if max == 0 || max > 65535 {
+            return Err(TextureError::Ppm(format!("invalid max value {}", max)));
+        }
+
+        // checked before reserving anything, the header alone can ask for gigabytes
+        let too_large = || TextureError::Ppm(format!("image too large: {}x{}", width, height));
+        let count = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|texels| texels.checked_mul(3))
+            .ok_or_else(too_large)?;
+        let truncated = || TextureError::Ppm("truncated pixel data".to_string());
+        let mut samples = Vec::new();
+        if binary {
+            // a single whitespace separates the header from the pixels
+            let data = bytes.get(pos + 1..).unwrap_or(&[]);
+            let size = if max < 256 { 1 } else { 2 };
+            if data.len() < count.checked_mul(size).ok_or_else(too_large)? {
+                return Err(truncated());
+            }
+            samples.reserve_exact(count);
+            for sample in data.chunks(size).take(count) {
+                samples.push(match sample {
+                    [value] => *value as u32,
+                    _ => (sample[0] as u32) << 8 | sample[1] as u32,
+                });
+            }
+        } else {
+            // every ascii sample takes a digit and a separator, more than the file can hold
+            // is truncated whatever comes next
+            if count > bytes.len() / 2 + 1 {
+                return Err(truncated());
+            }
+            samples.reserve_exact(count);
+            for _ in 0..count {
+                samples.push(number(token().map_err(|_| truncated())?)?);
+            }
+        }
+
+        let to_byte = |sample: u32| (sample.min(max) * 255 / max) as u8;
+        let mut pixels = Vec::with_capacity(count / 3 * 4);
+        for rgb in samples.chunks(3) {
+            pixels.extend_from_slice(&[to_byte(rgb[0]), to_byte(rgb[1]), to_byte(rgb[2]), 255]);
+        }
+        Texture::new(width, height, pixels)
+    }
+
+    // texel of a mip level, coordinates folded back into the image by the wrap mode
+    fn texel(&self, level: usize, x: i64, y: i64) -> [f64; 4] {
+        let level = &self.levels[level];
+        let (w, h) = (level.width as i64, level.height as i64);
+        let (x, y) = match self.wrap {
+            Wrap::Repeat => (x.rem_euclid(w), y.rem_euclid(h)),
+            Wrap::Clamp => (x.clamp(0, w - 1), y.clamp(0, h - 1)),
+        };
+        let i = ((y * w + x) * 4) as usize;
+        let mut res = [0.0; 4];
+        for (channel, byte) in res.iter_mut().zip(&level.pixels[i..i + 4]) {
+            *channel = *byte as f64 / 255.0;
+        }
+        res
+    }
+
+    // texel space of a mip level, texel centers at half integers, rows from the top
+    fn to_texels(&self, level: usize, uv: &Vec2D) -> (f64, f64) {
+        let level = &self.levels[level];
+        (
+            uv.x * level.width as f64,
+            (1.0 - uv.y) * level.height as f64,
+        )
+    }
+
+    fn nearest(&self, level: usize, uv: &Vec2D) -> [f64; 4] {
+        let (x, y) = self.to_texels(level, uv);
+        self.texel(level, x.floor() as i64, y.floor() as i64)
+    }
+
+    fn bilinear(&self, level: usize, uv: &Vec2D) -> [f64; 4] {
+        let (x, y) = self.to_texels(level, uv);
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let corners = [
+            (self.texel(level, x0, y0), (1.0 - tx) * (1.0 - ty)),
+            (self.texel(level, x0 + 1, y0), tx * (1.0 - ty)),
+            (self.texel(level, x0, y0 + 1), (1.0 - tx) * ty),
+            (self.texel(level, x0 + 1, y0 + 1), tx * ty),
+        ];
+        let mut res = [0.0; 4];
+        for (texel, weight) in corners.iter() {
+            for (channel, value) in res.iter_mut().zip(texel.iter()) {
+                *channel += value * weight;
+            }
+        }
+        res
+    }
+
+    // bilinear samples of the two levels around a fractional level of detail, blended
+    fn trilinear(&self, lod: f64, uv: &Vec2D) -> [f64; 4] {
+        let lod = lod.clamp(0.0, (self.levels.len() - 1) as f64);
+        let level = lod.floor() as usize;
+        let t = lod - level as f64;
+        let mut res = self.bilinear(level, uv);
+        if t > 0.0 {
+            let next = self.bilinear(level + 1, uv);
+            for (channel, value) in res.iter_mut().zip(next.iter()) {
+                *channel += (value - *channel) * t;
+            }
+        }
+        res
+    }
+
+    // RGBA in [0, 1] at `uv` from the full size image, no mipmapping
+    pub fn sample(&self, uv: &Vec2D) -> [f64; 4] {
+        self.sample_grad(uv, &Vec2D::default(), &Vec2D::default())
+    }
+
+    // RGBA in [0, 1] at `uv`, filtered according to the pixel footprint on the texture:
+    // how much uv changes to the next pixel right (`duv_dx`) and down (`duv_dy`)
+    pub fn sample_grad(&self, uv: &Vec2D, duv_dx: &Vec2D, duv_dy: &Vec2D) -> [f64; 4] {
+        if self.width() == 0 || self.height() == 0 || !uv.x.is_finite() || !uv.y.is_finite() {
+            return [1.0; 4];
+        }
+        match self.filter {
+            Filter::Nearest => self.nearest(0, uv),
+            Filter::Bilinear => self.bilinear(0, uv),
+            Filter::Trilinear => {
+                // footprint axes in texels of the full size image
+                let (w, h) = (self.width() as f64, self.height() as f64);
+                let axis_x = Vec2D::new(duv_dx.x * w, duv_dx.y * h);
+                let axis_y = Vec2D::new(duv_dy.x * w, duv_dy.y * h);
+                let (len_x, len_y) = (axis_x.length(), axis_y.length());
+                let (major, minor, major_axis) = if len_x >= len_y {
+                    (len_x, len_y, *duv_dx)
+                } else {
+                    (len_y, len_x, *duv_dy)
+                };
+                if !major.is_finite() {
+                    return self.bilinear(0, uv);
+                }
+
+                // a stretched footprint is covered by several samples along its major
+                // axis, each one sharp enough for the minor axis
+                let taps = match self.anisotropy {
+                    0 | 1 => 1,
+                    max => (major / minor.max(1e-9)).ceil().clamp(1.0, max as f64) as u32,
+                };
+                let lod = (major / taps as f64).max(1e-9).log2();
+                if taps == 1 {
+                    return self.trilinear(lod, uv);
+                }
+                let mut res = [0.0; 4];
+                for i in 0..taps {
+                    let offset = (i as f64 + 0.5) / taps as f64 - 0.5;
+                    let sample = self.trilinear(lod, &(*uv + major_axis.scale(offset)));
+                    for (channel, value) in res.iter_mut().zip(sample.iter()) {
+                        *channel += value / taps as f64;
+                    }
+                }
+                res
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f64; 4] = [1.0, 0.0, 0.0, 1.0];
+    const GREEN: [f64; 4] = [0.0, 1.0, 0.0, 1.0];
+    const WHITE: [f64; 4] = [1.0; 4];
+
+    // red, green on the top row, blue, white on the bottom one
+    fn two_by_two(filter: Filter, wrap: Wrap) -> Texture {
+        let pixels = vec![
+            255, 0, 0, 255, 0, 255, 0, 255, //
+            0, 0, 255, 255, 255, 255, 255, 255,
+        ];
+        let mut texture = Texture::new(2, 2, pixels).unwrap();
+        texture.filter = filter;
+        texture.wrap = wrap;
+        texture
+    }
+
+    fn assert_rgba_eq(a: [f64; 4], b: [f64; 4]) {
+        assert!(
+            a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-9),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn ascii_ppm() {
+        let texture = Texture::decode(b"P3\n# red and blue\n2 1\n15\n15 0 0\n0 0 15\n").unwrap();
+        assert_eq!((texture.width(), texture.height()), (2, 1));
+        assert_eq!(texture.pixels(), &[255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn binary_ppm() {
+        let mut bytes = b"P6 1 2 255\n".to_vec();
+        bytes.extend_from_slice(&[10, 20, 30, 40, 50, 60]);
+        let texture = Texture::decode(&bytes).unwrap();
+        assert_eq!((texture.width(), texture.height()), (1, 2));
+        assert_eq!(texture.pixels(), &[10, 20, 30, 255, 40, 50, 60, 255]);
+
+        // two bytes per sample above 255, most significant first
+        let mut bytes = b"P6 1 1 65535\n".to_vec();
+        bytes.extend_from_slice(&[0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+        assert_eq!(
+            Texture::decode(&bytes).unwrap().pixels(),
+            &[255, 127, 0, 255]
+        );
+    }
+
+    #[test]
+    fn truncated_ppm() {
+        let truncated = Err(TextureError::Ppm("truncated pixel data".to_string()));
+        assert_eq!(
+            Texture::from_ppm(b"P6 2 2 255\n\x01\x02\x03").map(|_| ()),
+            truncated
+        );
+        assert_eq!(
+            Texture::from_ppm(b"P3 2 1 255\n1 2 3 4").map(|_| ()),
+            truncated
+        );
+        assert_eq!(
+            Texture::from_ppm(b"P3 2").map(|_| ()),
+            Err(TextureError::Ppm("unexpected end of file".to_string()))
+        );
+    }
+
+    #[test]
+    fn huge_ppm_header() {
+        // rejected before anything is allocated for the pixels
+        for bytes in [
+            &b"P6 100000 100000 255\n\0\0\0"[..],
+            &b"P3 100000 100000 255\n0 0 0"[..],
+            &b"P6 4294967295 4294967295 65535\n"[..],
+        ] {
+            assert!(matches!(
+                Texture::from_ppm(bytes),
+                Err(TextureError::Ppm(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn png() {
+        // gray + alpha, expanded to RGBA
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+            encoder.set_color(png::ColorType::GrayscaleAlpha);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[50, 255, 200, 100]).unwrap();
+        }
+        let texture = Texture::decode(&bytes).unwrap();
+        assert_eq!(texture.pixels(), &[50, 50, 50, 255, 200, 200, 200, 100]);
+
+        assert!(matches!(
+            Texture::from_png(&bytes[..bytes.len() / 2]),
+            Err(TextureError::Png(_))
+        ));
+        assert_eq!(
+            Texture::decode(b"GIF89a").map(|_| ()),
+            Err(TextureError::UnknownFormat)
+        );
+    }
+
+    #[test]
+    fn nearest() {
+        let texture = two_by_two(Filter::Nearest, Wrap::Repeat);
+        assert_rgba_eq(texture.sample(&Vec2D::new(0.1, 0.9)), RED);
+        assert_rgba_eq(texture.sample(&Vec2D::new(0.9, 0.1)), WHITE);
+        // one image further right
+        assert_rgba_eq(texture.sample(&Vec2D::new(1.1, 0.9)), RED);
+
+        let texture = two_by_two(Filter::Nearest, Wrap::Clamp);
+        assert_rgba_eq(texture.sample(&Vec2D::new(1.1, 0.9)), GREEN);
+        assert_rgba_eq(
+            texture.sample(&Vec2D::new(-3.0, -3.0)),
+            [0.0, 0.0, 1.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn bilinear() {
+        let texture = two_by_two(Filter::Bilinear, Wrap::Repeat);
+        // exact at the texel centers, the average of the four in the middle
+        assert_rgba_eq(texture.sample(&Vec2D::new(0.25, 0.75)), RED);
+        assert_rgba_eq(texture.sample(&Vec2D::new(0.5, 0.5)), [0.5, 0.5, 0.5, 1.0]);
+        // the left border blends with the right column
+        assert_rgba_eq(texture.sample(&Vec2D::new(0.0, 0.75)), [0.5, 0.5, 0.0, 1.0]);
+
+        let texture = two_by_two(Filter::Bilinear, Wrap::Clamp);
+        assert_rgba_eq(texture.sample(&Vec2D::new(0.0, 0.75)), RED);
+    }
+
+    #[test]
+    fn mip_chain() {
+        for &(width, height, levels) in
+            [(5, 3, 3), (7, 1, 3), (1, 9, 4), (6, 6, 3), (1, 1, 1)].iter()
+        {
+            let texture =
+                Texture::new(width, height, vec![255; (width * height * 4) as usize]).unwrap();
+            assert_eq!(texture.levels(), levels, "{}x{}", width, height);
+            let last = texture.levels.last().unwrap();
+            assert_eq!((last.width, last.height), (1, 1));
+            // the borders that do not fit a 2x2 block are not lost
+            assert!(last.pixels.iter().all(|&v| v == 255));
+        }
+    }
+
+    #[test]
+    fn level_of_detail() {
+        // black and white checkerboard, every level past the first is gray
+        let pixels = (0..16)
+            .flat_map(|i| {
+                let v = if (i % 4 + i / 4) % 2 == 0 { 0 } else { 255 };
+                vec![v, v, v, 255]
+            })
+            .collect();
+        let texture = Texture::new(4, 4, pixels).unwrap();
+        assert_eq!(texture.filter, Filter::Trilinear);
+
+        // at the center of the bottom left texel, white, with more and more texels
+        // per pixel: the color fades to gray as the level of detail rises
+        let uv = Vec2D::new(0.125, 0.125);
+        let mut previous = 1.0;
+        for texels in [0.5, 1.0, 1.3, 1.6, 2.0, 4.0, 16.0].iter() {
+            let step = texels / 4.0;
+            let red = |duv_dx: Vec2D, duv_dy: Vec2D| texture.sample_grad(&uv, &duv_dx, &duv_dy)[0];
+            let along_x = red(Vec2D::new(step, 0.0), Vec2D::new(0.0, step / 2.0));
+            let along_y = red(Vec2D::new(step / 2.0, 0.0), Vec2D::new(0.0, step));
+            assert!((along_x - along_y).abs() < 1e-9);
+            assert!(along_x <= previous + 1e-9, "{} texels: {}", texels, along_x);
+            previous = along_x;
+        }
+        let sharp = texture.sample_grad(&uv, &Vec2D::new(0.25, 0.0), &Vec2D::new(0.0, 0.25));
+        assert_eq!(sharp[0], 1.0);
+        assert!((previous - 128.0 / 255.0).abs() < 1e-9);
+    }
+}
@@ -1,4 +1,7 @@
-use std::{f64::consts::PI, ops::Index};
+use std::{
+    f64::consts::PI,
+    ops::{Index, Mul},
+};
 
 use crate::vec3D::Vec3D;
 
@@ -37,10 +40,26 @@ impl Mat2D {
     }
 }
 
+// 4x4 matrix for row vectors: a point is transformed by `point.mul(&matrix)`
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Mat3D {
     data: [[f64; 4]; 4],
 }
 
+// a * b applies a first, then b
+impl Mul for Mat3D {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self {
+        let mut data = [[0.0; 4]; 4];
+        for (i, row) in data.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = (0..4).map(|k| self.data[i][k] * o.data[k][j]).sum();
+            }
+        }
+        Mat3D { data }
+    }
+}
+
 impl Index<u8> for Mat3D {
     type Output = [f64; 4];
 
@@ -56,6 +75,7 @@ impl Index<u8> for Mat3D {
 }
 
 impl Mat3D {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64, g: f64, h: f64, i: f64) -> Mat3D {
         Mat3D {
             data: [
@@ -67,6 +87,10 @@ impl Mat3D {
         }
     }
 
+    pub fn identity() -> Mat3D {
+        Mat3D::scale(1.0, 1.0, 1.0)
+    }
+
     pub fn rot_x(alpha: f64) -> Mat3D {
         Mat3D {
             data: [
@@ -110,6 +134,21 @@ impl Mat3D {
         }
     }
 
+    pub fn scale(x: f64, y: f64, z: f64) -> Mat3D {
+        Mat3D {
+            data: [
+                [x, 0.0, 0.0, 0.0],
+                [0.0, y, 0.0, 0.0],
+                [0.0, 0.0, z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn uniform_scale(s: f64) -> Mat3D {
+        Mat3D::scale(s, s, s)
+    }
+
     pub fn projection(fov: f64, aspect_ratio: f64, near: f64, far: f64) -> Mat3D {
         let fov_rad = 1.0 / (fov * PI / 360.0).tan();
         Mat3D {
@@ -122,6 +161,20 @@ impl Mat3D {
         }
     }
 
+    // parallel projection, `height` world units fit the screen vertically
+    // z is mapped from [near, far] to [0, 1] and w stays 1
+    pub fn orthographic(height: f64, aspect_ratio: f64, near: f64, far: f64) -> Mat3D {
+        let scale = 2.0 / height;
+        Mat3D {
+            data: [
+                [aspect_ratio * scale, 0.0, 0.0, 0.0],
+                [0.0, scale, 0.0, 0.0],
+                [0.0, 0.0, 1.0 / (far - near), 0.0],
+                [0.0, 0.0, -near / (far - near), 1.0],
+            ],
+        }
+    }
+
     pub fn quick_inverse(&self) -> Mat3D {
         Mat3D {
             data: [
@@ -138,6 +191,71 @@ impl Mat3D {
         }
     }
 
+    pub fn transpose(&self) -> Mat3D {
+        let mut data = [[0.0; 4]; 4];
+        for (i, row) in data.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = self.data[j][i];
+            }
+        }
+        Mat3D { data }
+    }
+
+    // determinant of the 3x3 matrix left after removing `row` and `col`
+    fn minor(&self, row: usize, col: usize) -> f64 {
+        let mut m = [[0.0; 3]; 3];
+        let rows = (0..4).filter(|&r| r != row);
+        for (mi, r) in rows.enumerate() {
+            let cols = (0..4).filter(|&c| c != col);
+            for (mj, c) in cols.enumerate() {
+                m[mi][mj] = self.data[r][c];
+            }
+        }
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // is_multiple_of would need rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn cofactor(&self, row: usize, col: usize) -> f64 {
+        let sign = if (row + col) % 2 == 0 { 1.0 } else { -1.0 };
+        sign * self.minor(row, col)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        (0..4).map(|j| self.data[0][j] * self.cofactor(0, j)).sum()
+    }
+
+    // inverse of any matrix, None when it squashes space (a zero scale for instance).
+    // quick_inverse is cheaper for rotations and translations only
+    pub fn inverse(&self) -> Option<Mat3D> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 || !det.is_finite() {
+            return None;
+        }
+        // adjugate: transposed cofactors
+        let mut data = [[0.0; 4]; 4];
+        for (i, row) in data.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = self.cofactor(j, i) / det;
+            }
+        }
+        Some(Mat3D { data })
+    }
+
+    // matrix for normals, which stay perpendicular to surfaces stretched by a
+    // non-uniform scale: inverse transpose without the translation. The result
+    // has to be normalized
+    pub fn normal_matrix(&self) -> Option<Mat3D> {
+        let mut linear = *self;
+        linear.data[3] = [0.0, 0.0, 0.0, 1.0];
+        for row in linear.data.iter_mut().take(3) {
+            row[3] = 0.0;
+        }
+        linear.inverse().map(|inverse| inverse.transpose())
+    }
+
     pub fn point_at(pos: &Vec3D, target: &Vec3D, up: &Vec3D) -> Mat3D {
         let new_forward = (*target - *pos).normalized();
 
@@ -156,3 +274,362 @@ impl Mat3D {
         }
     }
 }
+
+// Unit quaternion, a rotation without the gimbal lock of chained Euler angles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+// a * b rotates by b first, then by a
+impl Mul for Quat {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self {
+        Quat {
+            w: self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            x: self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            y: self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            z: self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        }
+    }
+}
+
+impl Quat {
+    pub fn identity() -> Quat {
+        Quat {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    // right handed rotation of `angle` radians around `axis`
+    pub fn from_axis_angle(axis: &Vec3D, angle: f64) -> Quat {
+        let axis = axis.normalized();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Quat {
+            w: cos,
+            x: axis.x * sin,
+            y: axis.y * sin,
+            z: axis.z * sin,
+        }
+    }
+
+    pub fn length(&self) -> f64 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    // accumulated products drift away from unit length
+    pub fn normalized(&self) -> Quat {
+        let l = self.length();
+        Quat {
+            w: self.w / l,
+            x: self.x / l,
+            y: self.y / l,
+            z: self.z / l,
+        }
+    }
+
+    // inverse rotation for a unit quaternion
+    pub fn conjugate(&self) -> Quat {
+        Quat {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    // rotation applied as Transform::rotation does: around x, then y, then z (radians)
+    pub fn from_euler(angles: &Vec3D) -> Quat {
+        // Mat3D::rot_y turns the other way around y
+        Quat::from_axis_angle(&Vec3D::new(0.0, 0.0, 1.0), angles.z)
+            * Quat::from_axis_angle(&Vec3D::new(0.0, 1.0, 0.0), -angles.y)
+            * Quat::from_axis_angle(&Vec3D::new(1.0, 0.0, 0.0), angles.x)
+    }
+
+    // back to Transform::rotation angles, y in [-pi / 2, pi / 2]
+    pub fn to_euler(&self) -> Vec3D {
+        let m = self.to_mat();
+        let y = m[0][2].clamp(-1.0, 1.0).asin();
+        if m[0][2].abs() < 1.0 - 1e-9 {
+            Vec3D::new(m[1][2].atan2(m[2][2]), y, m[0][1].atan2(m[0][0]))
+        } else {
+            // gimbal lock: x and z turn around the same axis, put it all on x
+            Vec3D::new((-m[2][1]).atan2(m[1][1]), y, 0.0)
+        }
+    }
+
+    // rotation matrix for row vectors: v.mul(&q.to_mat()) == q.rotate(&v)
+    pub fn to_mat(&self) -> Mat3D {
+        let Quat { w, x, y, z } = self.normalized();
+        Mat3D {
+            data: [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y + w * z),
+                    2.0 * (x * z - w * y),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * y - w * z),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z + w * x),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * z + w * y),
+                    2.0 * (y * z - w * x),
+                    1.0 - 2.0 * (x * x + y * y),
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    // rotation part of a matrix without scale, translation is ignored
+    pub fn from_mat(m: &Mat3D) -> Quat {
+        // largest of w, x, y, z first so the division stays accurate
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quat {
+                w: 0.25 * s,
+                x: (m[1][2] - m[2][1]) / s,
+                y: (m[2][0] - m[0][2]) / s,
+                z: (m[0][1] - m[1][0]) / s,
+            }
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Quat {
+                w: (m[1][2] - m[2][1]) / s,
+                x: 0.25 * s,
+                y: (m[1][0] + m[0][1]) / s,
+                z: (m[2][0] + m[0][2]) / s,
+            }
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Quat {
+                w: (m[2][0] - m[0][2]) / s,
+                x: (m[1][0] + m[0][1]) / s,
+                y: 0.25 * s,
+                z: (m[2][1] + m[1][2]) / s,
+            }
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Quat {
+                w: (m[0][1] - m[1][0]) / s,
+                x: (m[2][0] + m[0][2]) / s,
+                y: (m[2][1] + m[1][2]) / s,
+                z: 0.25 * s,
+            }
+        };
+        q.normalized()
+    }
+
+    pub fn dot(&self, o: &Quat) -> f64 {
+        self.w * o.w + self.x * o.x + self.y * o.y + self.z * o.z
+    }
+
+    fn scale(&self, s: f64) -> Quat {
+        Quat {
+            w: self.w * s,
+            x: self.x * s,
+            y: self.y * s,
+            z: self.z * s,
+        }
+    }
+
+    fn add(&self, o: &Quat) -> Quat {
+        Quat {
+            w: self.w + o.w,
+            x: self.x + o.x,
+            y: self.y + o.y,
+            z: self.z + o.z,
+        }
+    }
+
+    // q and -q are the same rotation, pick the one on the short way from self
+    pub fn closest(&self, o: &Quat) -> Quat {
+        if self.dot(o) < 0.0 {
+            o.scale(-1.0)
+        } else {
+            *o
+        }
+    }
+
+    // normalized straight line between the rotations, cheap but the angular speed
+    // is not constant
+    pub fn nlerp(&self, o: &Quat, t: f64) -> Quat {
+        let o = self.closest(o);
+        self.scale(1.0 - t).add(&o.scale(t)).normalized()
+    }
+
+    // constant angular speed along the shortest arc, t outside [0, 1] extrapolates
+    pub fn slerp(&self, o: &Quat, t: f64) -> Quat {
+        let o = self.closest(o);
+        let cos = self.dot(&o).min(1.0);
+        if cos > 0.9995 {
+            // nearly the same rotation, the arc is a straight line
+            return self.nlerp(&o, t);
+        }
+        let angle = cos.acos();
+        let sin = angle.sin();
+        self.scale(((1.0 - t) * angle).sin() / sin)
+            .add(&o.scale((t * angle).sin() / sin))
+            .normalized()
+    }
+
+    // logarithm of a unit quaternion: half the rotation angle along the axis
+    fn ln(&self) -> Quat {
+        let v = Vec3D::new(self.x, self.y, self.z);
+        let sin = v.length();
+        if sin < 1e-12 {
+            return Quat { w: 0.0, ..*self };
+        }
+        let half_angle = sin.atan2(self.w);
+        let v = v.scale(half_angle / sin);
+        Quat {
+            w: 0.0,
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+
+    fn exp(&self) -> Quat {
+        let v = Vec3D::new(self.x, self.y, self.z);
+        let half_angle = v.length();
+        if half_angle < 1e-12 {
+            return Quat::identity();
+        }
+        let v = v.scale(half_angle.sin() / half_angle);
+        Quat {
+            w: half_angle.cos(),
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+
+    // control point of a squad curve going through prev, self and next
+    pub fn squad_control(&self, prev: &Quat, next: &Quat) -> Quat {
+        let inv = self.conjugate();
+        let to_next = (inv * self.closest(next)).ln();
+        let to_prev = (inv * self.closest(prev)).ln();
+        (*self * to_next.add(&to_prev).scale(-0.25).exp()).normalized()
+    }
+
+    // smooth curve from self to o (spherical cubic), a and b are the squad_control of
+    // both ends
+    pub fn squad(&self, o: &Quat, a: &Quat, b: &Quat, t: f64) -> Quat {
+        self.slerp(o, t).slerp(&a.slerp(b, t), 2.0 * t * (1.0 - t))
+    }
+
+    // q * v * q^-1
+    pub fn rotate(&self, v: &Vec3D) -> Vec3D {
+        let p = Quat {
+            w: 0.0,
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        };
+        let r = *self * p * self.conjugate();
+        Vec3D::new(r.x, r.y, r.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mat_eq(a: &Mat3D, b: &Mat3D) {
+        for i in 0..4u8 {
+            for j in 0..4 {
+                assert!((a[i][j] - b[i][j]).abs() < 1e-9, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    fn assert_vec_eq(a: &Vec3D, b: &Vec3D) {
+        let d = *a - *b;
+        assert!(d.length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn inverse() {
+        let m = Mat3D::rot_x(0.3)
+            * Mat3D::rot_y(-1.1)
+            * Mat3D::scale(2.0, 0.5, 3.0)
+            * Mat3D::translation(1.0, -2.0, 5.0);
+        let inverse = m.inverse().unwrap();
+        assert_mat_eq(&(m * inverse), &Mat3D::identity());
+        assert_mat_eq(&(inverse * m), &Mat3D::identity());
+
+        let projection = Mat3D::projection(90.0, 4.0 / 3.0, 0.1, 100.0);
+        assert_mat_eq(
+            &(projection * projection.inverse().unwrap()),
+            &Mat3D::identity(),
+        );
+    }
+
+    #[test]
+    fn singular() {
+        let m = Mat3D::scale(2.0, 0.0, 1.0);
+        assert_eq!(m.determinant(), 0.0);
+        assert!(m.inverse().is_none());
+        assert!((Mat3D::scale(2.0, 3.0, 4.0).determinant() - 24.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn transpose() {
+        let m = Mat3D::rot_z(0.4) * Mat3D::translation(1.0, 2.0, 3.0);
+        assert_eq!(m.transpose()[3][1], m[1][3]);
+        assert_mat_eq(&m.transpose().transpose(), &m);
+        // a rotation is orthogonal
+        assert_mat_eq(
+            &Mat3D::rot_z(0.4).transpose(),
+            &Mat3D::rot_z(0.4).inverse().unwrap(),
+        );
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let angles = Vec3D::new(0.3, -0.7, 1.2);
+        assert_vec_eq(&Quat::from_euler(&angles).to_euler(), &angles);
+        // same rotation as the x, then y, then z matrices
+        assert_mat_eq(
+            &Quat::from_euler(&angles).to_mat(),
+            &(Mat3D::rot_x(angles.x) * Mat3D::rot_y(angles.y) * Mat3D::rot_z(angles.z)),
+        );
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let q = Quat::from_axis_angle(&Vec3D::new(1.0, 2.0, -0.5), 2.5);
+        let back = Quat::from_mat(&q.to_mat());
+        // q and -q are the same rotation
+        assert!(
+            (q.dot(&back).abs() - 1.0).abs() < 1e-9,
+            "{:?} != {:?}",
+            q,
+            back
+        );
+
+        let v = Vec3D::new(0.2, -1.0, 3.0);
+        assert_vec_eq(&v.mul(&q.to_mat()), &q.rotate(&v));
+    }
+
+    #[test]
+    fn slerp_ends() {
+        let a = Quat::from_euler(&Vec3D::new(0.1, 0.2, 0.3));
+        let b = Quat::from_euler(&Vec3D::new(-1.0, 0.5, 2.0));
+        assert_mat_eq(&a.slerp(&b, 0.0).to_mat(), &a.to_mat());
+        assert_mat_eq(&a.slerp(&b, 1.0).to_mat(), &b.to_mat());
+    }
+}
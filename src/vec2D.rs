@@ -0,0 +1,42 @@
+use std::ops::{Add, Sub};
+
+// texture coordinates and other 2 component values
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vec2D {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Add for Vec2D {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for Vec2D {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl Vec2D {
+    pub fn new(x: f64, y: f64) -> Vec2D {
+        Vec2D { x, y }
+    }
+
+    pub fn scale(self, alpha: f64) -> Vec2D {
+        Vec2D::new(self.x * alpha, self.y * alpha)
+    }
+
+    pub fn length(self) -> f64 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{vec2D::Vec2D, vec3D::Vec3D};
+
+// Everything a triangle corner carries besides its position.
+// Clipping interpolates these with the same parameter as the position.
+#[derive(Debug, Clone, Copy)]
+pub struct VertexAttr {
+    // zero when the model did not provide one
+    pub normal: Vec3D,
+    pub uv: Vec2D,
+    // filled by the pipeline: world position for per pixel lighting and the light
+    // computed at the corner for Gouraud shading
+    pub world: Vec3D,
+    pub color: [f64; 3],
+}
+
+impl Default for VertexAttr {
+    fn default() -> Self {
+        VertexAttr {
+            normal: Vec3D::new(0.0, 0.0, 0.0),
+            uv: Vec2D::default(),
+            world: Vec3D::new(0.0, 0.0, 0.0),
+            color: [0.0; 3],
+        }
+    }
+}
+
+impl VertexAttr {
+    pub fn new(normal: Vec3D, uv: Vec2D) -> VertexAttr {
+        VertexAttr {
+            normal,
+            uv,
+            ..VertexAttr::default()
+        }
+    }
+
+    // self at t = 0, other at t = 1
+    pub fn lerp(&self, other: &VertexAttr, t: f64) -> VertexAttr {
+        VertexAttr::barycentric(&[*self, *other, *other], &[1.0 - t, t, 0.0])
+    }
+
+    // weighted sum of the attributes of a triangle corners, weights summing to 1
+    pub fn barycentric(attrs: &[VertexAttr; 3], weights: &[f64; 3]) -> VertexAttr {
+        let mut res = VertexAttr::new(Vec3D::new(0.0, 0.0, 0.0), Vec2D::default());
+        for (attr, &weight) in attrs.iter().zip(weights.iter()) {
+            res.normal = res.normal + attr.normal.scale(weight);
+            res.uv = res.uv + attr.uv.scale(weight);
+            res.world = res.world + attr.world.scale(weight);
+            for (channel, value) in res.color.iter_mut().zip(attr.color.iter()) {
+                *channel += value * weight;
+            }
+        }
+        res
+    }
+}
+
+// A mesh corner: position plus attributes
+#[derive(Debug, Clone, Copy)]
+pub struct Vertex {
+    pub pos: Vec3D,
+    pub attr: VertexAttr,
+}
+
+impl Vertex {
+    pub fn new(pos: Vec3D, attr: VertexAttr) -> Vertex {
+        Vertex { pos, attr }
+    }
+}
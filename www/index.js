@@ -1,5 +1,8 @@
 import {
-    Scene
+    Scene,
+    Channel,
+    Interpolation,
+    RenderMode
 } from "wasm-game-of-life";
 
 const canvas = document.getElementById("canvas");
@@ -7,12 +10,18 @@ canvas.width = 1600;
 canvas.height = 900;
 
 const scene = Scene.new_teapot(canvas);
+const teapot = 0;
 let animationId = null;
-var tick_nb = 0
 
-const renderLoop = () => {
-    scene.tick(tick_nb);
-    tick_nb += 0.01;
+// three turns around x and two around y every 20 seconds, forever. Rotations take the
+// shortest way between keys, so there is one key per second
+for (let t = 0; t <= 20; t++) {
+    const turn = t / 20 * 2 * Math.PI;
+    scene.add_keyframe(teapot, Channel.Rotation, t, 3 * turn, 2 * turn, 0, Interpolation.Linear);
+}
+
+const renderLoop = (timestamp) => {
+    scene.tick((timestamp || 0) / 1000);
     animationId = requestAnimationFrame(renderLoop);
 };
 
@@ -21,20 +30,46 @@ const isPaused = () => {
 };
 
 const playPauseButton = document.getElementById("play-pause");
-const scene_input = (event) => {
-    scene.input(event.key);
+const key_down = (event) => {
+    scene.key_down(event.key);
+}
+const key_up = (event) => {
+    scene.key_up(event.key);
+}
+const mouse_down = () => {
+    scene.mouse_down();
+}
+const mouse_up = () => {
+    scene.mouse_up();
+}
+const mouse_move = (event) => {
+    scene.mouse_move(event.movementX, event.movementY);
+}
+const wheel = (event) => {
+    event.preventDefault();
+    scene.wheel(event.deltaY);
 }
 
 const play = () => {
     playPauseButton.textContent = "⏸";
-    document.addEventListener("keypress", scene_input);
+    document.addEventListener("keydown", key_down);
+    document.addEventListener("keyup", key_up);
+    canvas.addEventListener("mousedown", mouse_down);
+    document.addEventListener("mouseup", mouse_up);
+    document.addEventListener("mousemove", mouse_move);
+    canvas.addEventListener("wheel", wheel, { passive: false });
     renderLoop();
 };
 
 const pause = () => {
     playPauseButton.textContent = "▶";
     cancelAnimationFrame(animationId);
-    document.removeEventListener("keypress", scene_input);
+    document.removeEventListener("keydown", key_down);
+    document.removeEventListener("keyup", key_up);
+    canvas.removeEventListener("mousedown", mouse_down);
+    document.removeEventListener("mouseup", mouse_up);
+    document.removeEventListener("mousemove", mouse_move);
+    canvas.removeEventListener("wheel", wheel);
     animationId = null;
 };
 
@@ -46,4 +81,18 @@ playPauseButton.addEventListener("click", event => {
     }
 });
 
+document.getElementById("download").addEventListener("click", () => {
+    const png = scene.render_to_png(canvas.width, canvas.height);
+    const link = document.createElement("a");
+    link.href = URL.createObjectURL(new Blob([png], { type: "image/png" }));
+    link.download = "frame.png";
+    link.click();
+    URL.revokeObjectURL(link.href);
+});
+
+const renderMode = document.getElementById("render-mode");
+renderMode.addEventListener("change", () => {
+    scene.set_render_mode(RenderMode[renderMode.value]);
+});
+
 play();
\ No newline at end of file